@@ -1,4 +1,7 @@
-use clap::{clap_app, ArgMatches};
+use clap::{clap_app, Arg, ArgMatches, SubCommand};
+
+const DEFAULT_SOCKET: &str = "/tmp/taskmaster.sock";
+const DEFAULT_PIDFILE: &str = "/tmp/taskmaster.pid";
 
 fn file_exist(path: String) -> Result<(), String> {
     if std::fs::metadata(path).is_ok() {
@@ -8,6 +11,36 @@ fn file_exist(path: String) -> Result<(), String> {
     }
 }
 
+#[allow(clippy::needless_pass_by_value)]
+fn octal_mode(mode: String) -> Result<(), String> {
+    match u32::from_str_radix(&mode, 8) {
+        Ok(m) if m <= 0o7777 => Ok(()),
+        _ => Err(String::from("mode must be an octal number like 660")),
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn numeric_id(id: String) -> Result<(), String> {
+    id.parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| String::from("id must be a number"))
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn count(n: String) -> Result<(), String> {
+    n.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| String::from("must be a number"))
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn env_var(var: String) -> Result<(), String> {
+    match var.split_once('=') {
+        Some((key, _)) if !key.is_empty() => Ok(()),
+        _ => Err(String::from("must be like KEY=VALUE")),
+    }
+}
+
 pub fn generate() -> ArgMatches<'static> {
     clap_app!(app =>
         (version: "0.1")
@@ -17,10 +50,84 @@ pub fn generate() -> ArgMatches<'static> {
             (about: "Launch server daemon")
             (@arg config: <FILE> +takes_value {file_exist} "config file to use")
             (@arg format: -f --format possible_value[human yaml json] default_value[human] "set the message format")
-            (@arg detached: -d --detached "detached server")
+            (@arg detached: -d --detached "run the server as a daemon")
+            (@arg pidfile: --pidfile [FILE] default_value(DEFAULT_PIDFILE) "daemon pid file, locked while it runs")
+            (@arg clear_env: --("clear-env") "start the daemon without the current environment")
+            (@arg env: -e --env [VAR]... number_of_values(1) {env_var} "variable set in the daemon environment, as KEY=VALUE")
+            (@arg socket: -s --socket [FILE] default_value(DEFAULT_SOCKET) "control socket path")
+            (@arg socket_mode: --("socket-mode") [MODE] {octal_mode} "control socket permissions, in octal")
+            (@arg socket_owner: --("socket-owner") [UID] {numeric_id} "control socket owner")
+            (@arg socket_group: --("socket-group") [GID] {numeric_id} "control socket group")
+            (@arg no_auto_reload: --("no-auto-reload") "reload the config file only when asked, by the client or SIGHUP")
+            (@arg reload_debounce: --("reload-debounce") [MS] {count} default_value("500") "wait for the config file to be unchanged this long before reloading it")
+            (@arg state_file: --("state-file") [FILE] "file keeping the running children, to adopt them on restart")
+        )
+        (@subcommand check =>
+            (about: "Check a config file without starting it")
+            (@arg config: <FILE> +takes_value {file_exist} "config file to check")
         )
         (@subcommand client =>
             (about: "Launch client")
+            (@arg socket: -s --socket [FILE] default_value(DEFAULT_SOCKET) "control socket path")
+            (@subcommand start =>
+                (about: "start tasks")
+                (@arg tasks: <TASK>... "tasks to start")
+                (@arg wait: -w --wait "wait until the tasks are up")
+            )
+            (@subcommand stop =>
+                (about: "stop tasks")
+                (@arg tasks: <TASK>... "tasks to stop")
+                (@arg wait: -w --wait "wait until the tasks are stopped")
+            )
+            (@subcommand restart =>
+                (about: "restart tasks")
+                (@arg tasks: <TASK>... "tasks to restart")
+                (@arg wait: -w --wait "wait until the tasks are up")
+            )
+            (@subcommand pause =>
+                (about: "freeze the processes of tasks with SIGSTOP")
+                (@arg tasks: <TASK>... "tasks to pause")
+            )
+            (@subcommand resume =>
+                (about: "resume paused tasks with SIGCONT")
+                (@arg tasks: <TASK>... "tasks to resume")
+            )
+            (@subcommand status =>
+                (about: "show status of tasks")
+                (@arg tasks: <TASK>... "tasks to query")
+            )
+            (@subcommand info =>
+                (about: "get info on tasks")
+                (@arg tasks: <TASK>... "tasks to query")
+            )
+            (@subcommand tail =>
+                (about: "show the last lines printed by a task")
+                (@arg task: <TASK> "task to show")
+                (@arg lines: -n --lines [N] {count} default_value("10") "number of lines")
+            )
+            (@subcommand follow =>
+                (about: "show what a task prints, until interrupted")
+                (@arg task: <TASK> "task to follow")
+            )
+            (@subcommand signal =>
+                (about: "send a signal to the running processes of tasks")
+                (@arg signal: <SIGNAL> "signal to send, like HUP or SIGUSR1")
+                (@arg tasks: <TASK>... "tasks to signal")
+                (@arg instance: -i --instance [N] {numeric_id} "only signal this instance")
+            )
+            (@subcommand list => (about: "list all available tasks"))
+            (@subcommand reload =>
+                (about: "reload configuration file")
+                (@arg dry_run: --("dry-run") "only show what would change")
+            )
+            (subcommand: SubCommand::with_name("stop-server")
+                .about("stop the server")
+                .arg(Arg::with_name("keep_children")
+                    .long("keep-children")
+                    .help("leave the tasks running, for the next server to adopt them")))
+            (@subcommand handover =>
+                (about: "execute the server again in place, handing the running tasks over")
+            )
         )
     )
     .get_matches()
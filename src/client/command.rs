@@ -0,0 +1,209 @@
+use clap::ArgMatches;
+use std::{
+    io::{self, prelude::*},
+    thread, time,
+};
+
+use super::{
+    connect,
+    reply::{self, Reply},
+    request,
+};
+use crate::shared::message::Message;
+
+pub const EXIT_SUCCESS: i32 = 0;
+// the server answered with an error, like an unknown task
+pub const EXIT_ERROR: i32 = 1;
+// a task is in a failure state
+pub const EXIT_FAILED: i32 = 2;
+pub const EXIT_UNREACHABLE: i32 = 3;
+
+const POLL_DELAY: time::Duration = time::Duration::from_millis(100);
+// How long a start waits for the tasks on top of their successdelay,
+// their dependencies included
+const START_TIMEOUT: time::Duration = time::Duration::from_secs(30);
+
+// execute send a single command to the server and return the exit code
+pub fn execute(socket: &str, name: &str, matches: &ArgMatches<'static>) -> i32 {
+    let result = match name {
+        "list" => send(socket, &Message::List),
+        "reload" => send(
+            socket,
+            &Message::Reload {
+                dry_run: matches.is_present("dry_run"),
+            },
+        ),
+        "stop-server" => send(
+            socket,
+            &Message::Quit {
+                keep_children: matches.is_present("keep_children"),
+            },
+        ),
+        "handover" => send(socket, &Message::Handover),
+        "tail" => stream(
+            socket,
+            &Message::Tail {
+                id: matches.value_of("task").unwrap().to_string(),
+                lines: matches.value_of("lines").unwrap().parse().unwrap(),
+            },
+        ),
+        "signal" => matches.values_of("tasks").into_iter().flatten().try_fold(
+            EXIT_SUCCESS,
+            |code, taskname| {
+                let message = Message::Signal {
+                    id: taskname.to_string(),
+                    signal: matches.value_of("signal").unwrap().to_string(),
+                    instance: matches.value_of("instance").map(|n| n.parse().unwrap()),
+                };
+                send(socket, &message).map(|task_code| code.max(task_code))
+            },
+        ),
+        "follow" => stream(
+            socket,
+            &Message::Follow {
+                id: matches.value_of("task").unwrap().to_string(),
+            },
+        ),
+        _ => matches.values_of("tasks").into_iter().flatten().try_fold(
+            EXIT_SUCCESS,
+            |code, taskname| {
+                execute_task(socket, name, taskname, matches.is_present("wait"))
+                    .map(|task_code| code.max(task_code))
+            },
+        ),
+    };
+
+    result.unwrap_or_else(|e| {
+        eprintln!("cannot reach server on {socket}: {e}");
+        EXIT_UNREACHABLE
+    })
+}
+
+fn task_message(name: &str, taskname: &str) -> Message {
+    let id = taskname.to_string();
+
+    match name {
+        "start" => Message::Start { id },
+        "stop" => Message::Stop { id },
+        "restart" => Message::Restart { id },
+        "info" => Message::Info { id },
+        "pause" => Message::Pause { id },
+        "resume" => Message::Resume { id },
+        _ => Message::Status { id },
+    }
+}
+
+fn execute_task(socket: &str, name: &str, taskname: &str, wait: bool) -> io::Result<i32> {
+    let reply = send_and_parse(socket, &task_message(name, taskname))?;
+
+    match reply {
+        Reply::Error(_) => Ok(EXIT_ERROR),
+        reply if reply.is_failed_status() => Ok(EXIT_FAILED),
+        _ if !wait => Ok(EXIT_SUCCESS),
+        _ if name == "stop" => wait_stopped(socket, taskname),
+        _ => wait_started(socket, taskname),
+    }
+}
+
+fn send(socket: &str, message: &Message) -> io::Result<i32> {
+    match send_and_parse(socket, message)? {
+        Reply::Error(_) => Ok(EXIT_ERROR),
+        _ => Ok(EXIT_SUCCESS),
+    }
+}
+
+fn send_and_parse(socket: &str, message: &Message) -> io::Result<Reply> {
+    let raw = request(socket, message)?;
+
+    if !raw.is_empty() {
+        println!("{}", raw.trim_end());
+    }
+    Ok(reply::parse(&raw))
+}
+
+// stream print the reply as it comes, a follow only end with the server
+fn stream(socket: &str, message: &Message) -> io::Result<i32> {
+    let mut stream = connect(socket, message)?;
+    let mut buf = [0_u8; 4096];
+    let mut head: Option<String> = None;
+    let mut newline = true;
+
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let chunk = String::from_utf8_lossy(&buf[..n]);
+        print!("{chunk}");
+        io::stdout().flush()?;
+        newline = chunk.ends_with('\n');
+        head.get_or_insert_with(|| chunk.into_owned());
+    }
+    if !newline {
+        println!();
+    }
+    match head.map(|head| reply::parse(&head)) {
+        Some(Reply::Error(_)) => Ok(EXIT_ERROR),
+        _ => Ok(EXIT_SUCCESS),
+    }
+}
+
+fn status(socket: &str, taskname: &str) -> io::Result<Reply> {
+    let raw = request(
+        socket,
+        &Message::Status {
+            id: taskname.to_string(),
+        },
+    )?;
+    Ok(reply::parse(&raw))
+}
+
+// A started task is considered up once it is active, every child having run
+// for its successdelay
+fn wait_started(socket: &str, taskname: &str) -> io::Result<i32> {
+    let info = reply::parse(&request(
+        socket,
+        &Message::Info {
+            id: taskname.to_string(),
+        },
+    )?);
+    let successdelay = match info {
+        Reply::Task { successdelay } => successdelay.unwrap_or(0),
+        _ => 0,
+    };
+    let deadline =
+        time::Instant::now() + time::Duration::from_secs(successdelay.into()) + START_TIMEOUT;
+
+    loop {
+        thread::sleep(POLL_DELAY);
+        let current = status(socket, taskname)?;
+        if current.is_failed_status() {
+            println!("{taskname} failed");
+            return Ok(EXIT_FAILED);
+        }
+        if current.is_not_running() {
+            println!("{taskname} is not running");
+            return Ok(EXIT_FAILED);
+        }
+        if current.is_started() {
+            println!("{taskname} started");
+            return Ok(EXIT_SUCCESS);
+        }
+        if time::Instant::now() >= deadline {
+            println!("{taskname} did not start in time");
+            return Ok(EXIT_FAILED);
+        }
+    }
+}
+
+fn wait_stopped(socket: &str, taskname: &str) -> io::Result<i32> {
+    loop {
+        thread::sleep(POLL_DELAY);
+        if let Reply::Status { status, .. } = status(socket, taskname)? {
+            if !reply::has_status(&status, "stopping") {
+                println!("{} {}", taskname, status.to_lowercase());
+                return Ok(EXIT_SUCCESS);
+            }
+        }
+    }
+}
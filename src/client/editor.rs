@@ -26,7 +26,7 @@ impl Editor {
         let pressed_key = read_key()?;
 
         match pressed_key {
-            Key::Char(c) if c == '\n' => self.newline = true,
+            Key::Char('\n') => self.newline = true,
             Key::Char(c) => line.push(c),
             Key::Up if line.is_empty() => {
                 if let Some(cmd) = history.get(&Direction::Previous) {
@@ -44,7 +44,7 @@ impl Editor {
             Key::Ctrl('d') => self.should_quit = true,
             Key::Ctrl('c') => {
                 if line.is_empty() {
-                    println!("")
+                    println!();
                 }
                 line.clear();
             }
@@ -109,7 +109,7 @@ fn refresh_screen() -> Result<(), std::io::Error> {
 }
 
 fn display_line(line: &str) {
-    print!("{}", line);
+    print!("{line}");
     io::stdout().flush().unwrap();
 }
 
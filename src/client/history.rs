@@ -26,7 +26,7 @@ impl History {
         match direction {
             Direction::Next => self.pos -= 1,
             Direction::Previous => self.pos += 1,
-        };
+        }
         if let Some(line) = self.queue.get(self.pos) {
             return Some(line.clone());
         }
@@ -34,6 +34,6 @@ impl History {
     }
 
     pub fn print(&self) {
-        self.queue.iter().for_each(|line| println!("{}", line));
+        self.queue.iter().for_each(|line| println!("{line}"));
     }
 }
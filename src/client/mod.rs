@@ -1,111 +1,254 @@
 use std::io::{self, prelude::*, BufReader, ErrorKind};
 
+mod command;
 mod editor;
 mod history;
+mod reply;
 
 use crate::shared::{error, message::Message};
 
+pub use self::command::execute;
 use self::{editor::Editor, history::History};
 use std::os::unix::net::UnixStream;
 
 type Result<T> = std::result::Result<T, error::Taskmaster>;
 
-fn send_message(msg: &Message) -> io::Result<()> {
-    let mut stream = UnixStream::connect("/tmp/taskmaster.sock")?;
+const DEFAULT_TAIL: usize = 10;
+
+fn connect(socket: &str, msg: &Message) -> io::Result<UnixStream> {
+    let mut stream = UnixStream::connect(socket)?;
     let serialized = serde_json::to_string(&msg).unwrap();
 
     stream.write_all(serialized.as_bytes())?;
-    let reader = BufReader::new(stream);
+    Ok(stream)
+}
+
+// request send a message and wait for the whole reply
+fn request(socket: &str, msg: &Message) -> io::Result<String> {
+    let mut reply = String::new();
+
+    connect(socket, msg)?.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+fn send_message(socket: &str, msg: &Message) -> io::Result<()> {
+    let reader = BufReader::new(connect(socket, msg)?);
     for line in reader.lines() {
         println!("{}", line?);
     }
     Ok(())
 }
 
-fn process_line(history: &History, line: &str) -> Result<()> {
-    let vec: Vec<&str> = line.split(' ').collect();
+// one arm for each command
+#[allow(clippy::too_many_lines)]
+fn process_line(socket: &str, history: &History, line: &str) -> Result<()> {
+    // quotes keep a selector like 'worker-*' in one word
+    let words =
+        shell_words::split(line).unwrap_or_else(|_| line.split(' ').map(String::from).collect());
+    let vec: Vec<&str> = words.iter().map(String::as_str).collect();
 
-    match *vec.get(0).unwrap() {
-        "list" => send_message(&Message::List)?,
-        "reload" => send_message(&Message::Reload)?,
+    match vec.first().copied().unwrap_or_default() {
+        "list" => send_message(socket, &Message::List)?,
+        "reload" => send_message(
+            socket,
+            &Message::Reload {
+                dry_run: vec.contains(&"--dry-run"),
+            },
+        )?,
         "history" => history.print(),
         "help" => print_help(),
+        "handover" => send_message(socket, &Message::Handover)?,
         "stop-server" => {
-            send_message(&Message::Quit)?;
+            send_message(
+                socket,
+                &Message::Quit {
+                    keep_children: vec.contains(&"--keep-children"),
+                },
+            )?;
             return Ok(());
         }
         "start" => {
             if vec.len() > 1 {
                 for taskname in vec.iter().skip(1) {
-                    send_message(&Message::Start {
-                        id: (*taskname).to_string(),
-                    })?;
+                    send_message(
+                        socket,
+                        &Message::Start {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
                 }
             }
         }
         "info" => {
             if vec.len() > 1 {
                 for taskname in vec.iter().skip(1) {
-                    send_message(&Message::Info {
-                        id: (*taskname).to_string(),
-                    })?;
+                    send_message(
+                        socket,
+                        &Message::Info {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
                 }
             }
         }
         "stop" => {
             if vec.len() > 1 {
                 for taskname in vec.iter().skip(1) {
-                    send_message(&Message::Stop {
-                        id: (*taskname).to_string(),
-                    })?;
+                    send_message(
+                        socket,
+                        &Message::Stop {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
                 }
             }
         }
         "status" => {
             if vec.len() > 1 {
                 for taskname in vec.iter().skip(1) {
-                    send_message(&Message::Status {
-                        id: (*taskname).to_string(),
-                    })?;
+                    send_message(
+                        socket,
+                        &Message::Status {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
                 }
             }
         }
+        "tail" => {
+            if vec.len() > 1 {
+                send_message(socket, &tail_message(&vec[1..])?)?;
+            }
+        }
+        "signal" => {
+            for message in signal_messages(&vec[1..])? {
+                send_message(socket, &message)?;
+            }
+        }
+        "follow" => {
+            if let Some(taskname) = vec.get(1) {
+                send_message(
+                    socket,
+                    &Message::Follow {
+                        id: (*taskname).to_string(),
+                    },
+                )?;
+            }
+        }
         "restart" => {
             if vec.len() > 1 {
                 for taskname in vec.iter().skip(1) {
-                    send_message(&Message::Restart {
-                        id: (*taskname).to_string(),
-                    })?;
+                    send_message(
+                        socket,
+                        &Message::Restart {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
+                }
+            }
+        }
+        "pause" => {
+            if vec.len() > 1 {
+                for taskname in vec.iter().skip(1) {
+                    send_message(
+                        socket,
+                        &Message::Pause {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
+                }
+            }
+        }
+        "resume" => {
+            if vec.len() > 1 {
+                for taskname in vec.iter().skip(1) {
+                    send_message(
+                        socket,
+                        &Message::Resume {
+                            id: (*taskname).to_string(),
+                        },
+                    )?;
                 }
             }
         }
         _ => {
-            println!("Invalid command: {}", line);
+            println!("Invalid command: {line}");
             return Err(error::Taskmaster::InvalidCmd);
         }
-    };
+    }
     Ok(())
 }
 
+fn tail_message(args: &[&str]) -> Result<Message> {
+    let lines = match args.get(1).map(|n| n.parse()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            println!("Invalid line count: {}", args[1]);
+            return Err(error::Taskmaster::InvalidCmd);
+        }
+        None => DEFAULT_TAIL,
+    };
+
+    Ok(Message::Tail {
+        id: args[0].to_string(),
+        lines,
+    })
+}
+
+// signal_messages parse "<signal> <task>... [--instance N]"
+fn signal_messages(args: &[&str]) -> Result<Vec<Message>> {
+    let mut args = args.to_vec();
+    let mut instance = None;
+
+    if let Some(i) = args.iter().position(|arg| *arg == "--instance") {
+        let Some(Ok(n)) = args.get(i + 1).map(|n| n.parse()) else {
+            println!("Invalid instance: {}", args.get(i + 1).unwrap_or(&""));
+            return Err(error::Taskmaster::InvalidCmd);
+        };
+        instance = Some(n);
+        args.drain(i..=i + 1);
+    }
+    let Some((signal, tasks)) = args.split_first().filter(|(_, tasks)| !tasks.is_empty()) else {
+        println!("Usage: signal <signal> <task>... [--instance N]");
+        return Err(error::Taskmaster::InvalidCmd);
+    };
+
+    Ok(tasks
+        .iter()
+        .map(|taskname| Message::Signal {
+            id: (*taskname).to_string(),
+            signal: (*signal).to_string(),
+            instance,
+        })
+        .collect())
+}
+
 fn print_help() {
-    let s = r#"Usage:
+    let s = r"Usage: <task> is a task name, group:<name> for every task of a group,
+        a glob like 'worker-*', a regex like /^api-/, or all for every task
         start: start the task <task>
         stop: stop the task <task>
         restart: restart the task <task>
-        reload: reload configuration file
+        pause: freeze the processes of <task>, keeping them in memory
+        resume: resume the paused processes of <task>
+        reload: reload configuration file, with --dry-run to only show what would change
         list: list all available tasks
         info: get info on <task>
         history: display previous valid commands
         help: show this help menu
         status: show status of <command>
-        stop-server: stop the server
+        tail: show the last [n] lines printed by <task>
+        follow: show what <task> prints, until interrupted
+        signal: send <signal> to the processes of <task>, with --instance N for only one
+        stop-server: stop the server, with --keep-children to leave the tasks running
+        handover: execute the server again in place, handing the running tasks over
         exit: exit client
-        "#;
-    print!("{}", s);
+        ";
+    print!("{s}");
 }
 
-pub fn start() -> Result<()> {
-    if UnixStream::connect("/tmp/taskmaster.sock").is_ok() {
+pub fn start(socket: &str) -> Result<()> {
+    if UnixStream::connect(socket).is_ok() {
         let mut history = History::new();
 
         loop {
@@ -115,20 +258,20 @@ pub fn start() -> Result<()> {
                     return Ok(());
                 }
                 Ok(line) => {
-                    log::debug!("line={}", line);
-                    let res = process_line(&history, &line);
+                    log::debug!("line={line}");
+                    let res = process_line(socket, &history, &line);
                     if res.is_ok() {
                         history.push(line);
                         continue;
                     }
                     match res {
-                        Err(error::Taskmaster::InvalidCmd) => continue,
+                        Err(error::Taskmaster::InvalidCmd) => {}
                         e => return e,
                     }
                 }
                 Err(e) if e.kind() == ErrorKind::Interrupted => return Ok(()),
                 Err(e) => {
-                    log::error!("got error {:?}", e);
+                    log::error!("got error {e:?}");
                     panic!("unexpected error")
                 }
             }
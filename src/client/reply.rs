@@ -0,0 +1,186 @@
+use serde::Deserialize;
+
+// What the client needs to know about a server reply, whatever its format
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Error(String),
+    Status { taskid: String, status: String },
+    Task { successdelay: Option<u32> },
+    Other,
+}
+
+impl Reply {
+    pub fn is_failed_status(&self) -> bool {
+        matches!(self, Reply::Status { status, .. } if failed_status(status))
+    }
+
+    // is_started is true once every task is active, its children up for their successdelay
+    pub fn is_started(&self) -> bool {
+        matches!(self, Reply::Status { status, .. } if statuses(status).all(|status| status == "active"))
+    }
+
+    // is_not_running is true for a task that won't start by itself anymore
+    pub fn is_not_running(&self) -> bool {
+        matches!(self, Reply::Status { status, .. } if statuses(status).any(|status| {
+            matches!(status.as_str(), "finished" | "stopped" | "inactive")
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct TaskReply {
+    successdelay: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum StructuredReply {
+    Error {
+        message: String,
+    },
+    Status {
+        taskid: String,
+        status: String,
+    },
+    Selection {
+        selector: String,
+        status: String,
+    },
+    Task {
+        task: TaskReply,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl From<StructuredReply> for Reply {
+    fn from(reply: StructuredReply) -> Self {
+        match reply {
+            StructuredReply::Error { message } => Reply::Error(message),
+            StructuredReply::Status { taskid, status } => Reply::Status { taskid, status },
+            StructuredReply::Selection { selector, status } => Reply::Status {
+                taskid: selector,
+                status,
+            },
+            StructuredReply::Task { task } => Reply::Task {
+                successdelay: task.successdelay,
+            },
+            StructuredReply::Other => Reply::Other,
+        }
+    }
+}
+
+pub fn failed_status(status: &str) -> bool {
+    statuses(status).any(|status| {
+        matches!(
+            status.as_str(),
+            "failed" | "failing" | "backoff" | "fatal" | "unhealthy"
+        )
+    })
+}
+
+pub fn has_status(status: &str, expected: &str) -> bool {
+    statuses(status).any(|status| status == expected)
+}
+
+// statuses split the summary of several tasks, like "2 active, 1 failed"
+fn statuses(status: &str) -> impl Iterator<Item = String> + '_ {
+    status
+        .split(", ")
+        .filter_map(|part| part.rsplit(' ').next())
+        .map(str::to_lowercase)
+}
+
+// Json replies are valid yaml, human replies are scanned line by line
+pub fn parse(raw: &str) -> Reply {
+    serde_yaml::from_str::<StructuredReply>(raw).map_or_else(|_| parse_human(raw), Reply::from)
+}
+
+fn parse_human(raw: &str) -> Reply {
+    if let Some(message) = raw.strip_prefix("error: ") {
+        return Reply::Error(message.trim_end().to_string());
+    }
+    if let Some(status) = raw
+        .lines()
+        .next()
+        .and_then(|l| l.strip_prefix("status of "))
+    {
+        if let Some((taskid, status)) = status.trim_end().rsplit_once(": ") {
+            return Reply::Status {
+                taskid: taskid.to_string(),
+                status: status.to_string(),
+            };
+        }
+    }
+    if raw.starts_with("Info ") {
+        let successdelay = raw
+            .lines()
+            .find_map(|line| line.strip_prefix("Success Delay: "))
+            .and_then(|delay| delay.trim().parse().ok());
+        return Reply::Task { successdelay };
+    }
+    Reply::Other
+}
+
+#[cfg(test)]
+mod test_reply {
+    use super::{parse, Reply};
+
+    #[test]
+    fn test_parse_structured() {
+        assert_eq!(
+            parse(r#"{"type":"error","message":"unknown taskid foo"}"#),
+            Reply::Error(String::from("unknown taskid foo"))
+        );
+        assert_eq!(
+            parse("---\ntype: status\ntaskid: web\nstatus: Failed\n"),
+            Reply::Status {
+                taskid: String::from("web"),
+                status: String::from("Failed")
+            }
+        );
+        assert_eq!(
+            parse(r#"{"type":"task","taskid":"web","task":{"cmd":"ls","successdelay":3}}"#),
+            Reply::Task {
+                successdelay: Some(3)
+            }
+        );
+        assert_eq!(
+            parse(r#"{"type":"selection","selector":"group:web","status":"1 active","tasks":{}}"#),
+            Reply::Status {
+                taskid: String::from("group:web"),
+                status: String::from("1 active")
+            }
+        );
+        assert_eq!(parse(r#"{"type":"tasks","tasks":{}}"#), Reply::Other);
+    }
+
+    #[test]
+    fn test_parse_human() {
+        assert_eq!(
+            parse("error: unknown taskid foo"),
+            Reply::Error(String::from("unknown taskid foo"))
+        );
+        assert!(parse("status of web: failed").is_failed_status());
+        assert!(parse("status of web: failing\nretries: 1\n").is_failed_status());
+        assert!(parse("status of web: fatal").is_failed_status());
+        assert!(!parse("status of web: active").is_failed_status());
+        assert!(
+            parse("status of group:web: 2 active, 1 fatal\n    - db: fatal\n").is_failed_status()
+        );
+        assert!(!parse("status of group:web: 2 active, 1 stopped").is_failed_status());
+        assert_eq!(
+            parse("Info web:\nCommand: ls\nSuccess Delay: 2\nExit Codes: [0]"),
+            Reply::Task {
+                successdelay: Some(2)
+            }
+        );
+        assert_eq!(parse("Available jobs:\n    - web\n"), Reply::Other);
+
+        assert!(parse("status of web: active").is_started());
+        assert!(!parse("status of web: starting").is_started());
+        assert!(!parse("status of group:web: 1 active, 1 waiting").is_started());
+        assert!(parse("status of group:web: 2 active, 1 finished").is_not_running());
+        assert!(!parse("status of web: waiting").is_not_running());
+    }
+}
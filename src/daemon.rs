@@ -0,0 +1,207 @@
+use nix::{
+    fcntl::{flock, FlockArg, OFlag},
+    sys::wait::waitpid,
+    unistd::{dup2, fork, getpid, pipe2, setsid, ForkResult},
+};
+use std::{
+    env,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{prelude::*, BufReader, SeekFrom},
+    os::unix::io::{AsRawFd, FromRawFd},
+    path::PathBuf,
+    process,
+};
+
+use crate::shared::error;
+
+type DaemonResult<T> = Result<T, error::Taskmaster>;
+
+// Daemon is how the server runs in the background
+// env is set on top of the inherited environment, or replace it with clear_env
+// a daemon handed over to is one already, and nobody waits for it to be ready
+pub struct Daemon {
+    pub handed_over: bool,
+    pub pidfile: PathBuf,
+    pub clear_env: bool,
+    pub env: Vec<(String, String)>,
+}
+
+// Ready tell the waiting parent the daemon started
+pub struct Ready {
+    pipe: Option<File>,
+}
+
+impl Ready {
+    pub fn notify(self) {
+        if let Some(mut pipe) = self.pipe {
+            drop(writeln!(pipe, "ok {}", getpid()));
+        }
+    }
+
+    fn failed(self, e: &error::Taskmaster) {
+        if let Some(mut pipe) = self.pipe {
+            drop(writeln!(pipe, "{}", reason(e)));
+        }
+    }
+}
+
+// the parent wraps the reason in its own error
+fn reason(e: &error::Taskmaster) -> String {
+    match e {
+        error::Taskmaster::Daemon(reason) => reason.clone(),
+        e => e.to_string(),
+    }
+}
+
+// the fds given by the os are owned by nobody else
+fn owned(fd: i32) -> File {
+    unsafe { File::from_raw_fd(fd) }
+}
+
+fn failure(what: &str, e: impl Display) -> error::Taskmaster {
+    error::Taskmaster::Daemon(format!("{what}: {e}"))
+}
+
+impl Daemon {
+    // start run the server in a daemon, the parent exits once it is ready or failed
+    pub fn start<F>(&self, run: F) -> DaemonResult<()>
+    where
+        F: FnOnce(Ready) -> DaemonResult<()>,
+    {
+        if self.handed_over {
+            return self.run(Ready { pipe: None }, run);
+        }
+        let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(|e| failure("cannot pipe", e))?;
+
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                drop(owned(write));
+                if let Err(e) = waitpid(child, None) {
+                    log::debug!("cannot wait for child-{child}: {e}");
+                }
+                wait_ready(owned(read))
+            }
+            // the children report to the parent, which prints their errors
+            Ok(ForkResult::Child) => {
+                drop(owned(read));
+                let ready = Ready {
+                    pipe: Some(owned(write)),
+                };
+
+                // the session leader exits, so the daemon never gets a terminal back
+                if let Err(e) = setsid() {
+                    ready.failed(&failure("cannot create a session", e));
+                    process::exit(1);
+                }
+                match unsafe { fork() } {
+                    Ok(ForkResult::Parent { .. }) => process::exit(0),
+                    Ok(ForkResult::Child) => {
+                        process::exit(i32::from(self.run(ready, run).is_err()))
+                    }
+                    Err(e) => {
+                        ready.failed(&failure("cannot fork", e));
+                        process::exit(1);
+                    }
+                }
+            }
+            Err(_) => Err(error::Taskmaster::ForkFailed),
+        }
+    }
+
+    fn run<F>(&self, ready: Ready, run: F) -> DaemonResult<()>
+    where
+        F: FnOnce(Ready) -> DaemonResult<()>,
+    {
+        let pidfile = match self.setup() {
+            Ok(pidfile) => pidfile,
+            Err(e) => {
+                log::error!("{e}");
+                ready.failed(&e);
+                return Err(e);
+            }
+        };
+
+        let mut report = ready.pipe.as_ref().map(File::try_clone).transpose()?;
+        let result = run(ready);
+
+        // the parent only reads the first line, it is already gone if the server was ready
+        if let (Err(e), Some(report)) = (&result, &mut report) {
+            drop(writeln!(report, "{}", reason(e)));
+        }
+        drop(report);
+        drop(fs::remove_file(&self.pidfile));
+        drop(pidfile);
+        result
+    }
+
+    // setup lock the pidfile, set the environment and leave the terminal
+    fn setup(&self) -> DaemonResult<File> {
+        let pidfile = self.lock()?;
+
+        if self.clear_env {
+            for (key, _) in env::vars_os() {
+                env::remove_var(key);
+            }
+        }
+        for (key, value) in &self.env {
+            env::set_var(key, value);
+        }
+        let null = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/null")
+            .map_err(|e| failure("cannot open /dev/null", e))?;
+        for fd in 0..=2 {
+            dup2(null.as_raw_fd(), fd).map_err(|e| failure("cannot redirect stdio", e))?;
+        }
+        Ok(pidfile)
+    }
+
+    // lock hold the pidfile for the life of the daemon, so only one runs at a time
+    fn lock(&self) -> DaemonResult<File> {
+        let path = self.pidfile.display();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.pidfile)
+            .map_err(|e| failure(&format!("cannot open pidfile {path}"), e))?;
+
+        if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
+            let mut pid = String::new();
+            drop(file.read_to_string(&mut pid));
+            return Err(error::Taskmaster::Daemon(format!(
+                "already running with pid {}, see {}",
+                pid.trim(),
+                path
+            )));
+        }
+        file.set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| writeln!(file, "{}", getpid()))
+            .map_err(|e| failure(&format!("cannot write pidfile {path}"), e))?;
+        Ok(file)
+    }
+}
+
+// wait_ready wait for the daemon to tell how its start went
+fn wait_ready(pipe: File) -> DaemonResult<()> {
+    let mut reply = String::new();
+
+    drop(BufReader::new(pipe).read_line(&mut reply));
+    let reply = reply.trim_end();
+    if reply.is_empty() {
+        return Err(error::Taskmaster::Daemon(String::from(
+            "exited before being ready",
+        )));
+    }
+    match reply.strip_prefix("ok ") {
+        Some(pid) => {
+            println!("server started with pid {pid}");
+            Ok(())
+        }
+        None => Err(error::Taskmaster::Daemon(reply.to_string())),
+    }
+}
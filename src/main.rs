@@ -4,83 +4,149 @@ extern crate serde;
 
 mod cli;
 mod client;
+mod daemon;
 mod server;
 mod shared;
 
+use daemon::Daemon;
 use log::{LevelFilter, SetLoggerError};
-use nix::unistd::{close, execve, fork, setsid, ForkResult};
+use nix::unistd::{Gid, Uid};
 use shared::{
     error,
     logger::{self, Config},
 };
-use std::{env, ffi::CString, fs::File, time};
+use std::{
+    fs::{File, OpenOptions},
+    path::PathBuf,
+    time,
+};
 
 type TaskmasterResult<T> = Result<T, error::Taskmaster>;
 
-fn init(cli: &clap::ArgMatches<'static>) -> Result<(), SetLoggerError> {
+// Where a daemon logs without --log-file, its stdout being closed
+const DAEMON_LOGFILE: &str = "/tmp/taskmaster.log";
+
+// a daemon appends to its log, so a second one failing to start doesn't erase it
+fn init(logfile: Option<&str>, append: bool) -> Result<(), SetLoggerError> {
     let config = Config::new(Some(time::Instant::now()));
 
-    cli.value_of("logfile").map_or_else(
+    logfile.map_or_else(
         || logger::simple::Logger::init(LevelFilter::Debug, config),
-        |file| logger::file::Logger::init(LevelFilter::Debug, config, File::create(file).unwrap()),
+        |file| {
+            let file = if append {
+                OpenOptions::new().create(true).append(true).open(file)
+            } else {
+                File::create(file)
+            };
+            logger::file::Logger::init(LevelFilter::Debug, config, file.unwrap())
+        },
     )
 }
 
-fn detach(path: String, config: &str, logfile: Option<&str>) -> TaskmasterResult<()> {
-    match unsafe { fork() } {
-        Ok(ForkResult::Parent { child, .. }) => {
-            println!("Child is {}", child);
-            Ok(())
-        }
-        Ok(ForkResult::Child) => {
-            println!("I'm in Child Process");
-            let mut args = vec![
-                CString::new("taskmaster").unwrap(),
-                CString::new("--log-file").unwrap(),
-            ];
-            if let Some(file) = logfile {
-                args.push(CString::new(file).unwrap());
-            } else {
-                args.push(CString::new("/tmp/taskmaster.log").unwrap());
-            }
-            args.push(CString::new("server").unwrap());
-            args.push(CString::new(config).unwrap());
-            close(0).unwrap();
-            close(1).unwrap();
-            close(2).unwrap();
-            setsid().unwrap();
-            execve(
-                CString::new(path).unwrap().as_c_str(),
-                args.as_slice(),
-                &[CString::new("").unwrap()],
-            )
-            .expect("Failed to launch process");
-            Ok(())
-        }
-        Err(_) => Err(error::Taskmaster::ForkFailed),
+// values are checked by the cli validators
+fn watch_config(matches: &clap::ArgMatches<'static>) -> server::WatchConfig {
+    server::WatchConfig {
+        auto_reload: !matches.is_present("no_auto_reload"),
+        debounce: time::Duration::from_millis(
+            matches
+                .value_of("reload_debounce")
+                .unwrap()
+                .parse()
+                .unwrap(),
+        ),
+    }
+}
+
+// values are checked by the cli validators
+fn daemon(matches: &clap::ArgMatches<'static>, handed_over: bool) -> Daemon {
+    Daemon {
+        handed_over,
+        pidfile: PathBuf::from(matches.value_of("pidfile").unwrap()),
+        clear_env: matches.is_present("clear_env"),
+        env: matches
+            .values_of("env")
+            .into_iter()
+            .flatten()
+            .filter_map(|var| var.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+// values are checked by the cli validators
+fn socket_config(matches: &clap::ArgMatches<'static>) -> server::SocketConfig {
+    server::SocketConfig {
+        path: PathBuf::from(matches.value_of("socket").unwrap()),
+        mode: matches
+            .value_of("socket_mode")
+            .map(|mode| u32::from_str_radix(mode, 8).unwrap()),
+        owner: matches
+            .value_of("socket_owner")
+            .map(|uid| Uid::from_raw(uid.parse().unwrap())),
+        group: matches
+            .value_of("socket_group")
+            .map(|gid| Gid::from_raw(gid.parse().unwrap())),
     }
 }
 
 fn main() -> TaskmasterResult<()> {
+    let handed_over = server::handed_over();
     let cli = cli::generate();
-    let mut args = env::args();
-    init(&cli).unwrap();
+    let detached = cli
+        .subcommand_matches("server")
+        .is_some_and(|matches| matches.is_present("detached"));
+    let logfile = cli
+        .value_of("logfile")
+        .or_else(|| detached.then_some(DAEMON_LOGFILE));
+    // the log of the previous server is kept on a handover
+    init(logfile, detached || handed_over).unwrap();
 
     match cli.subcommand() {
         ("server", Some(matches)) => {
             let config = matches.value_of("config").unwrap();
             let format = matches.value_of("format").unwrap();
-            if matches.is_present("detached") {
-                log::info!("detached");
-                let logfile = cli.value_of("logfile");
-                detach(args.next().unwrap(), config, logfile)
+            let socket = socket_config(matches);
+            let watch = watch_config(matches);
+            let state_file = matches.value_of("state_file").map(PathBuf::from);
+
+            if detached {
+                daemon(matches, handed_over).start(|ready| {
+                    server::start(
+                        config,
+                        format,
+                        &socket,
+                        watch,
+                        state_file,
+                        handed_over,
+                        || {
+                            ready.notify();
+                        },
+                    )
+                })
             } else {
-                server::start(config, format)
+                server::start(
+                    config,
+                    format,
+                    &socket,
+                    watch,
+                    state_file,
+                    handed_over,
+                    || {},
+                )
             }
         }
-        ("client", Some(_matches)) => {
+        ("check", Some(matches)) => {
+            // only the problems are shown, not how the file is parsed
+            log::set_max_level(LevelFilter::Warn);
+            std::process::exit(server::check(matches.value_of("config").unwrap()));
+        }
+        ("client", Some(matches)) => {
+            let socket = matches.value_of("socket").unwrap();
+            if let (name, Some(command)) = matches.subcommand() {
+                std::process::exit(client::execute(socket, name, command));
+            }
             log::info!("starting client");
-            client::start()
+            client::start(socket)
         }
         _ => {
             log::error!("unknown subcommand");
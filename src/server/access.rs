@@ -0,0 +1,141 @@
+use super::nix_utils;
+use crate::shared::message::Message;
+use nix::unistd::{Gid, Uid};
+use serde::{Deserialize, Serialize};
+
+// Credentials of the process on the other side of the control socket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peer {
+    pub uid: Uid,
+    pub gid: Gid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+    All,
+    Reload,
+    Start,
+    Info,
+    Stop,
+    Status,
+    Restart,
+    Pause,
+    Resume,
+    List,
+    Quit,
+    Handover,
+    Tail,
+    Follow,
+    Signal,
+}
+
+impl From<&Message> for Permission {
+    fn from(message: &Message) -> Self {
+        match message {
+            Message::Reload { .. } => Permission::Reload,
+            Message::Start { .. } => Permission::Start,
+            Message::Info { .. } => Permission::Info,
+            Message::Stop { .. } => Permission::Stop,
+            Message::Status { .. } => Permission::Status,
+            Message::Restart { .. } => Permission::Restart,
+            Message::Pause { .. } => Permission::Pause,
+            Message::Resume { .. } => Permission::Resume,
+            Message::List => Permission::List,
+            Message::Quit { .. } => Permission::Quit,
+            Message::Handover => Permission::Handover,
+            Message::Tail { .. } => Permission::Tail,
+            Message::Follow { .. } => Permission::Follow,
+            Message::Signal { .. } => Permission::Signal,
+        }
+    }
+}
+
+// A rule without uid nor gid match every peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRule {
+    #[serde(with = "nix_utils::SerdeOptionnalUidGid", default)]
+    pub uid: Option<Uid>,
+    #[serde(with = "nix_utils::SerdeOptionnalUidGid", default)]
+    pub gid: Option<Gid>,
+    pub allow: Vec<Permission>,
+}
+
+impl AccessRule {
+    fn matches(&self, peer: Peer) -> bool {
+        self.uid.is_none_or(|uid| uid == peer.uid) && self.gid.is_none_or(|gid| gid == peer.gid)
+    }
+
+    fn allows(&self, permission: Permission) -> bool {
+        self.allow
+            .iter()
+            .any(|&allowed| allowed == Permission::All || allowed == permission)
+    }
+}
+
+// root and the user running the server can do anything, others need a matching rule
+pub fn is_allowed(rules: &[AccessRule], owner: Uid, peer: Peer, message: &Message) -> bool {
+    if peer.uid.is_root() || peer.uid == owner {
+        return true;
+    }
+    let permission = Permission::from(message);
+    rules
+        .iter()
+        .any(|rule| rule.matches(peer) && rule.allows(permission))
+}
+
+#[cfg(test)]
+mod test_access {
+    use super::{is_allowed, AccessRule, Peer, Permission};
+    use crate::shared::message::Message;
+    use nix::unistd::{Gid, Uid};
+
+    fn peer(uid: u32, gid: u32) -> Peer {
+        Peer {
+            uid: Uid::from_raw(uid),
+            gid: Gid::from_raw(gid),
+        }
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let owner = Uid::from_raw(1000);
+        let rules = vec![
+            AccessRule {
+                uid: Some(Uid::from_raw(1001)),
+                gid: None,
+                allow: vec![Permission::Status, Permission::List],
+            },
+            AccessRule {
+                uid: None,
+                gid: Some(Gid::from_raw(50)),
+                allow: vec![Permission::All],
+            },
+        ];
+        let status = Message::Status {
+            id: String::from("foo"),
+        };
+        let quit = Message::Quit {
+            keep_children: false,
+        };
+
+        assert!(is_allowed(&rules, owner, peer(0, 0), &quit));
+        assert!(is_allowed(&[], owner, peer(1000, 1000), &quit));
+        assert!(!is_allowed(&[], owner, peer(1001, 1001), &status));
+
+        assert!(is_allowed(&rules, owner, peer(1001, 1001), &status));
+        assert!(is_allowed(&rules, owner, peer(1001, 1001), &Message::List));
+        assert!(!is_allowed(&rules, owner, peer(1001, 1001), &quit));
+
+        let hup = Message::Signal {
+            id: String::from("foo"),
+            signal: String::from("HUP"),
+            instance: None,
+        };
+        assert!(!is_allowed(&rules, owner, peer(1001, 1001), &hup));
+        assert!(is_allowed(&rules, owner, peer(1002, 50), &hup));
+
+        assert!(is_allowed(&rules, owner, peer(1002, 50), &quit));
+        assert!(!is_allowed(&rules, owner, peer(1002, 51), &status));
+    }
+}
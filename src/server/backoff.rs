@@ -0,0 +1,76 @@
+use std::{
+    collections::hash_map::RandomState,
+    convert::TryInto,
+    hash::{BuildHasher, Hasher},
+    time,
+};
+
+// Delay before the next restart: initial * multiplier^attempt, capped to max,
+// then spread by up to jitter percent either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: u32,
+    pub max: u32,
+    pub multiplier: u32,
+    pub jitter: u32,
+}
+
+impl Backoff {
+    pub fn delay(&self, attempt: u32) -> time::Duration {
+        self.delay_with(attempt, random_unit())
+    }
+
+    // random is in [0, 1)
+    fn delay_with(&self, attempt: u32, random: f64) -> time::Duration {
+        let factor = f64::from(self.multiplier).powi(attempt.try_into().unwrap_or(i32::MAX));
+        let delay = (f64::from(self.initial) * factor).min(f64::from(self.max));
+        let jitter = f64::from(self.jitter.min(100)) / 100.0;
+
+        time::Duration::from_secs_f64(delay * (1.0 - jitter + 2.0 * jitter * random))
+    }
+}
+
+// RandomState is seeded by the OS, which is enough to spread restarts
+#[allow(clippy::cast_precision_loss)]
+fn random_unit() -> f64 {
+    let random = RandomState::new().build_hasher().finish() >> 11;
+
+    random as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod test_backoff {
+    use super::{random_unit, Backoff};
+    use std::time::Duration;
+
+    #[test]
+    fn test_delay() {
+        let backoff = Backoff {
+            initial: 1,
+            max: 10,
+            multiplier: 2,
+            jitter: 0,
+        };
+
+        assert_eq!(backoff.delay_with(0, 0.5), Duration::from_secs(1));
+        assert_eq!(backoff.delay_with(3, 0.5), Duration::from_secs(8));
+        assert_eq!(backoff.delay_with(4, 0.5), Duration::from_secs(10));
+        assert_eq!(backoff.delay_with(u32::MAX, 0.5), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let backoff = Backoff {
+            initial: 10,
+            max: 60,
+            multiplier: 2,
+            jitter: 10,
+        };
+
+        assert_eq!(backoff.delay_with(0, 0.0), Duration::from_secs(9));
+        assert_eq!(backoff.delay_with(0, 0.5), Duration::from_secs(10));
+        let delay = backoff.delay(0);
+        assert!(delay >= Duration::from_secs(9) && delay <= Duration::from_secs(11));
+        assert!((0.0..1.0).contains(&random_unit()));
+    }
+}
@@ -0,0 +1,102 @@
+use nix::unistd::{access, AccessFlags};
+use std::{
+    convert::TryFrom,
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use super::{task::ConfigFile, watcher::Watcher};
+
+// check parse a config file like the server does, then look for what would
+// fail once the tasks are started, return the exit code
+pub fn check(path: &str) -> i32 {
+    let problems = match Watcher::try_from(path).and_then(|watcher| ConfigFile::try_from(&watcher))
+    {
+        Ok(configfile) => configfile
+            .order
+            .iter()
+            .flat_map(|name| {
+                configfile.tasks[name]
+                    .check()
+                    .into_iter()
+                    .map(move |problem| format!("[{name}] {problem}"))
+            })
+            .collect(),
+        Err(e) => vec![e.to_string()],
+    };
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    if problems.is_empty() {
+        println!("{path} is valid");
+        0
+    } else {
+        1
+    }
+}
+
+// find_program resolve a command like execvp would, from the working directory
+// or the PATH of the task
+pub fn find_program(program: &str, workingdir: &Path, path: Option<&OsStr>) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(workingdir.join(program));
+    }
+    let path = path.map_or_else(|| env::var_os("PATH"), |path| Some(path.to_os_string()))?;
+
+    env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file() && access(path, AccessFlags::X_OK).is_ok()
+}
+
+// is_writable tell whether an output file can be opened, its name may hold placeholders
+pub fn is_writable(file: &Path) -> Result<(), String> {
+    if file.exists() {
+        return access(file, AccessFlags::W_OK)
+            .map_err(|e| format!("{} is not writable: {}", file.display(), e));
+    }
+    let dir = match file.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    if !dir.is_dir() {
+        return Err(format!("directory {} doesn't exist", dir.display()));
+    }
+    access(dir, AccessFlags::W_OK)
+        .map_err(|e| format!("directory {} is not writable: {}", dir.display(), e))
+}
+
+#[cfg(test)]
+mod test_check {
+    use super::{find_program, is_executable, is_writable};
+    use std::{ffi::OsStr, path::Path};
+
+    #[test]
+    fn test_find_program() {
+        let sh = find_program("sh", Path::new("/"), Some(OsStr::new("/nowhere:/bin"))).unwrap();
+
+        assert!(is_executable(&sh));
+        assert_eq!(
+            find_program("sh", Path::new("/"), Some(OsStr::new("/nowhere"))),
+            None
+        );
+        assert_eq!(
+            find_program("./run.sh", Path::new("/srv"), None),
+            Some(Path::new("/srv/./run.sh").to_path_buf())
+        );
+        assert!(!is_executable(Path::new("/etc/passwd")));
+        assert!(!is_executable(Path::new("/")));
+    }
+
+    #[test]
+    fn test_is_writable() {
+        assert!(is_writable(&std::env::temp_dir().join("taskmaster-check.log")).is_ok());
+        assert!(is_writable(Path::new("/nowhere/out.log")).is_err());
+    }
+}
@@ -1,4 +1,4 @@
 pub enum Com {
     End,
-    Msg(String)
+    Msg(String),
 }
@@ -1,4 +1,9 @@
-use super::relaunch::Relaunch;
+use super::{
+    output::OutputMode,
+    relaunch::Relaunch,
+    schedule::{Missed, Overlap},
+    stop::StopSignal,
+};
 use nix::sys::{signal::Signal, stat::Mode};
 use std::{collections::BTreeMap, path::PathBuf};
 
@@ -6,6 +11,10 @@ pub fn autostart() -> bool {
     false
 }
 
+pub fn shell() -> bool {
+    false
+}
+
 pub fn numprocess() -> u32 {
     1
 }
@@ -30,18 +39,58 @@ pub fn exit_codes() -> Vec<i32> {
     vec![0]
 }
 
+pub fn priority() -> u32 {
+    999
+}
+
 pub fn success_delay() -> u32 {
     0
 }
 
-pub fn stop_signal() -> Signal {
-    Signal::SIGTERM
+pub fn backoff_max() -> u32 {
+    60
+}
+
+pub fn backoff_multiplier() -> u32 {
+    2
+}
+
+pub fn backoff_jitter() -> u32 {
+    10
+}
+
+pub fn health_interval() -> u32 {
+    10
+}
+
+pub fn health_timeout() -> u32 {
+    2
+}
+
+pub fn health_retries() -> u32 {
+    3
+}
+
+pub fn overlap() -> Overlap {
+    Overlap::Skip
+}
+
+pub fn missed() -> Missed {
+    Missed::Skip
+}
+
+pub fn stop_signal() -> StopSignal {
+    StopSignal::Single(Signal::SIGTERM)
 }
 
 pub fn stop_delay() -> u32 {
     2
 }
 
+pub fn as_group() -> bool {
+    false
+}
+
 pub fn stdout() -> String {
     String::from("/dev/null")
 }
@@ -50,6 +99,14 @@ pub fn stderr() -> String {
     String::from("/dev/null")
 }
 
+pub fn backups() -> u32 {
+    10
+}
+
+pub fn output_mode() -> OutputMode {
+    OutputMode::Truncate
+}
+
 pub fn env() -> BTreeMap<String, String> {
     BTreeMap::new()
 }
@@ -0,0 +1,135 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+};
+
+use super::task::Task;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencyError {
+    Unknown { task: String, dependency: String },
+    Cycle(Vec<String>),
+}
+
+impl Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencyError::Unknown { task, dependency } => {
+                write!(f, "task {task} depends on unknown task {dependency}")
+            }
+            DependencyError::Cycle(tasks) => {
+                write!(f, "dependency cycle {} -> {}", tasks.join(" -> "), tasks[0])
+            }
+        }
+    }
+}
+
+// order sort the tasks so each one comes after its dependencies, lower priorities first
+pub fn order(tasks: &BTreeMap<String, Task>) -> Result<Vec<String>, DependencyError> {
+    let mut waiting: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+
+    for (name, task) in tasks {
+        for dependency in &task.depends_on {
+            if !tasks.contains_key(dependency) {
+                return Err(DependencyError::Unknown {
+                    task: name.clone(),
+                    dependency: dependency.clone(),
+                });
+            }
+        }
+        waiting.insert(name, task.depends_on.iter().map(String::as_str).collect());
+    }
+
+    let mut order = Vec::new();
+    loop {
+        let next = waiting
+            .iter()
+            .filter(|(_, dependencies)| dependencies.is_empty())
+            .map(|(name, _)| (tasks[*name].priority, *name))
+            .min();
+
+        match next {
+            Some((_, name)) => {
+                waiting.remove(name);
+                for dependencies in waiting.values_mut() {
+                    dependencies.remove(name);
+                }
+                order.push(name.to_string());
+            }
+            None if waiting.is_empty() => return Ok(order),
+            None => return Err(find_cycle(&waiting)),
+        }
+    }
+}
+
+// every task left waits on another one left, so following them loops back
+fn find_cycle(waiting: &BTreeMap<&str, BTreeSet<&str>>) -> DependencyError {
+    let mut path: Vec<&str> = vec![waiting.keys().next().unwrap()];
+
+    loop {
+        let last = path[path.len() - 1];
+        let next = waiting[last].iter().next().unwrap();
+
+        if let Some(start) = path.iter().position(|name| name == next) {
+            return DependencyError::Cycle(path[start..].iter().map(ToString::to_string).collect());
+        }
+        path.push(next);
+    }
+}
+
+#[cfg(test)]
+mod test_dependency {
+    use super::{order, DependencyError};
+    use crate::server::task::ConfigFile;
+
+    fn parse(config: &str) -> ConfigFile {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    #[test]
+    fn test_order() {
+        let config = parse(
+            "web:\n  cmd: echo\n  depends_on: [db, cache]\n\
+             cache:\n  cmd: echo\n  priority: 10\n\
+             db:\n  cmd: echo\n\
+             cron:\n  cmd: echo\n  priority: 1\n",
+        );
+
+        assert_eq!(
+            order(&config.tasks).unwrap(),
+            ["cron", "cache", "db", "web"]
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        let config = parse("web:\n  cmd: echo\n  depends_on: [db]\n");
+
+        assert_eq!(
+            order(&config.tasks),
+            Err(DependencyError::Unknown {
+                task: String::from("web"),
+                dependency: String::from("db"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let config = parse(
+            "a:\n  cmd: echo\n  depends_on: [b]\n\
+             b:\n  cmd: echo\n  depends_on: [c]\n\
+             c:\n  cmd: echo\n  depends_on: [b]\n",
+        );
+        let err = order(&config.tasks).unwrap_err();
+
+        assert_eq!(
+            err,
+            DependencyError::Cycle(vec![String::from("b"), String::from("c")])
+        );
+        assert_eq!(err.to_string(), "dependency cycle b -> c -> b");
+
+        let config = parse("a:\n  cmd: echo\n  depends_on: [a]\n");
+        assert!(order(&config.tasks).is_err());
+    }
+}
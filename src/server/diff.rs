@@ -0,0 +1,157 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+use super::task::Task;
+
+// Change is a task field whose value differs in the new config
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+// ReloadDiff is what a reload would do to each monitor, tasks are in config order
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ReloadDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub reloaded: BTreeMap<String, Vec<Change>>,
+    pub unchanged: Vec<String>,
+}
+
+impl ReloadDiff {
+    pub fn new<'a>(
+        current: impl IntoIterator<Item = (&'a String, &'a Task)>,
+        order: &[String],
+        tasks: &BTreeMap<String, Task>,
+    ) -> Self {
+        let current: BTreeMap<&String, &Task> = current.into_iter().collect();
+        let mut diff = Self::default();
+
+        for name in order {
+            match current.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(task) if *task == &tasks[name] => diff.unchanged.push(name.clone()),
+                Some(task) => {
+                    diff.reloaded
+                        .insert(name.clone(), changes(task, &tasks[name]));
+                }
+            }
+        }
+        diff.removed = current
+            .keys()
+            .filter(|name| !tasks.contains_key(name.as_str()))
+            .map(|name| (*name).clone())
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.reloaded.is_empty()
+    }
+}
+
+// changes compare the tasks as written in a config file, field by field
+pub fn changes(old: &Task, new: &Task) -> Vec<Change> {
+    let old = fields(old);
+    let new = fields(new);
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|field| {
+            let old = old.get(field).cloned().unwrap_or(Value::Null);
+            let new = new.get(field).cloned().unwrap_or(Value::Null);
+
+            (old != new).then(|| Change {
+                field: field.clone(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+fn fields(task: &Task) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(task) {
+        Ok(Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod test_diff {
+    use super::{changes, Change, ReloadDiff};
+    use crate::server::task::Task;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn task(raw: &str) -> Task {
+        serde_yaml::from_str(raw).unwrap()
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = task("{cmd: sleep 1, numprocess: 2}");
+        let new = task("{cmd: sleep 2, numprocess: 2, env: {A: b}}");
+
+        assert_eq!(changes(&old, &old), vec![]);
+        assert_eq!(
+            changes(&old, &new),
+            vec![
+                Change {
+                    field: String::from("cmd"),
+                    old: json!("sleep 1"),
+                    new: json!("sleep 2"),
+                },
+                Change {
+                    field: String::from("env"),
+                    old: json!({}),
+                    new: json!({"A": "b"}),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reload_diff() {
+        let names = |names: &[&str]| names.iter().map(ToString::to_string).collect();
+        let current: BTreeMap<String, Task> = vec![
+            (String::from("kept"), task("{cmd: ls}")),
+            (String::from("changed"), task("{cmd: ls}")),
+            (String::from("gone"), task("{cmd: ls}")),
+        ]
+        .into_iter()
+        .collect();
+        let tasks: BTreeMap<String, Task> = vec![
+            (String::from("kept"), task("{cmd: ls}")),
+            (String::from("changed"), task("{cmd: ls, autostart: true}")),
+            (String::from("new"), task("{cmd: ls}")),
+        ]
+        .into_iter()
+        .collect();
+        let order: Vec<String> = names(&["new", "changed", "kept"]);
+
+        let diff = ReloadDiff::new(&current, &order, &tasks);
+        assert_eq!(diff.added, names(&["new"]));
+        assert_eq!(diff.removed, names(&["gone"]));
+        assert_eq!(diff.unchanged, names(&["kept"]));
+        assert_eq!(diff.reloaded["changed"].len(), 1);
+        assert_eq!(diff.reloaded["changed"][0].field, "autostart");
+        assert!(!diff.is_empty());
+
+        let diff = ReloadDiff::new(&tasks, &order, &tasks);
+        assert!(diff.is_empty());
+    }
+}
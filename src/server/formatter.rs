@@ -1,7 +1,13 @@
-use super::{communication::Com, monitor::Status, task::Task};
+use super::{
+    communication::Com,
+    diff::ReloadDiff,
+    monitor::TaskStatus,
+    tail::LogLine,
+    task::{get_current_timestamp, Task},
+};
 use serde::Serialize;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt,
     str::FromStr,
     sync::mpsc::{SendError, Sender},
@@ -22,7 +28,7 @@ impl FromStr for MessageFormat {
             "human" => Ok(MessageFormat::Human),
             "yaml" => Ok(MessageFormat::Yaml),
             "json" => Ok(MessageFormat::Json),
-            _ => Err(format!("Unknown Message format {}", s)),
+            _ => Err(format!("Unknown Message format {s}")),
         }
     }
 }
@@ -39,11 +45,34 @@ impl fmt::Display for MessageFormat {
 
 #[derive(Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
+#[allow(clippy::large_enum_variant)]
 enum Message {
-    Error { message: String },
-    Status { taskid: String, status: Status },
-    Tasks { tasks: HashMap<String, Task> },
-    Task { taskid: String, task: Task },
+    Error {
+        message: String,
+    },
+    Status {
+        taskid: String,
+        #[serde(flatten)]
+        status: TaskStatus,
+    },
+    Selection {
+        selector: String,
+        status: String,
+        tasks: BTreeMap<String, TaskStatus>,
+    },
+    Tasks {
+        tasks: HashMap<String, Task>,
+    },
+    Diff(ReloadDiff),
+    Task {
+        taskid: String,
+        task: Task,
+    },
+    Line {
+        taskid: String,
+        #[serde(flatten)]
+        line: LogLine,
+    },
 }
 
 impl Message {
@@ -51,13 +80,21 @@ impl Message {
         Self::Error { message }
     }
 
-    fn from_status(name: String, status: Status) -> Self {
+    fn from_status(name: String, status: TaskStatus) -> Self {
         Self::Status {
             taskid: name,
             status,
         }
     }
 
+    fn from_selection(selector: String, tasks: BTreeMap<String, TaskStatus>) -> Self {
+        Self::Selection {
+            selector,
+            status: summary(&tasks),
+            tasks,
+        }
+    }
+
     fn from_tasks_iter(tasks: &mut impl Iterator<Item = (String, Task)>) -> Self {
         Self::from_tasks(tasks.collect())
     }
@@ -69,18 +106,72 @@ impl Message {
     fn from_task(name: String, task: Task) -> Self {
         Self::Task { taskid: name, task }
     }
+
+    fn from_line(name: String, line: LogLine) -> Self {
+        Self::Line { taskid: name, line }
+    }
+}
+
+// summary count the selected tasks in each status, like "2 active, 1 failed"
+fn summary(tasks: &BTreeMap<String, TaskStatus>) -> String {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+
+    for status in tasks.values() {
+        *counts.entry(status.status.to_string()).or_default() += 1;
+    }
+    counts
+        .iter()
+        .map(|(status, count)| format!("{count} {status}"))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 type SenderResult = Result<(), SendError<Com>>;
 
 pub trait Formatter {
     fn send_task(sender: &Sender<Com>, name: &str, task: &Task) -> SenderResult;
-    fn send_status(sender: &Sender<Com>, name: &str, status: Status) -> SenderResult;
+    fn send_status(sender: &Sender<Com>, name: &str, status: &TaskStatus) -> SenderResult;
+    fn send_statuses(
+        sender: &Sender<Com>,
+        selector: &str,
+        tasks: BTreeMap<String, TaskStatus>,
+    ) -> SenderResult;
     fn send_tasks(
         sender: &Sender<Com>,
         tasks: &mut impl Iterator<Item = (String, Task)>,
     ) -> SenderResult;
     fn send_error(sender: &Sender<Com>, message: String) -> SenderResult;
+    fn send_reload_diff(sender: &Sender<Com>, diff: &ReloadDiff) -> SenderResult;
+    fn format_line(name: &str, line: &LogLine, prefixed: bool) -> String;
+
+    // the lines of several tasks are mixed, so they must tell their task apart
+    fn format_tagged_line(name: &str, line: &LogLine, prefixed: bool) -> String {
+        Self::format_line(name, line, prefixed)
+    }
+
+    fn send_lines(
+        sender: &Sender<Com>,
+        name: &str,
+        lines: &[LogLine],
+        prefixed: bool,
+    ) -> SenderResult {
+        for line in lines {
+            sender.send(Com::Msg(Self::format_line(name, line, prefixed)))?;
+        }
+        Ok(())
+    }
+
+    fn send_tagged_lines(
+        sender: &Sender<Com>,
+        name: &str,
+        lines: &[LogLine],
+        prefixed: bool,
+    ) -> SenderResult {
+        for line in lines {
+            sender.send(Com::Msg(Self::format_tagged_line(name, line, prefixed)))?;
+        }
+        Ok(())
+    }
 }
 
 pub struct Human;
@@ -92,22 +183,92 @@ impl Formatter for Human {
     ) -> SenderResult {
         sender.send(Com::Msg(String::from("Available jobs:\n")))?;
         for (name, _task) in tasks {
-            sender.send(Com::Msg(format!("    - {}\n", name)))?;
+            sender.send(Com::Msg(format!("    - {name}\n")))?;
+        }
+        Ok(())
+    }
+
+    fn send_status(sender: &Sender<Com>, name: &str, status: &TaskStatus) -> SenderResult {
+        sender.send(Com::Msg(format!("status of {}: {}\n", name, status.status)))?;
+        sender.send(Com::Msg(format!("retries: {}\n", status.retry_count)))?;
+        if let Some(last_exit) = &status.last_exit {
+            sender.send(Com::Msg(format!("last exit: {last_exit}\n")))?;
+        }
+        if let Some(health) = &status.health {
+            sender.send(Com::Msg(format!("health: {health}\n")))?;
+        }
+        if let Some(next_run) = status.next_run {
+            let delay = next_run.saturating_sub(get_current_timestamp().as_secs());
+            sender.send(Com::Msg(format!("next run: at {next_run}, in {delay}s\n")))?;
+        }
+        for child in &status.children {
+            sender.send(Com::Msg(format!("{child}\n")))?;
         }
         Ok(())
     }
 
-    fn send_status(sender: &Sender<Com>, name: &str, status: Status) -> SenderResult {
-        sender.send(Com::Msg(format!("status of {}: {}", name, status)))
+    fn send_statuses(
+        sender: &Sender<Com>,
+        selector: &str,
+        tasks: BTreeMap<String, TaskStatus>,
+    ) -> SenderResult {
+        sender.send(Com::Msg(format!(
+            "status of {}: {}\n",
+            selector,
+            summary(&tasks)
+        )))?;
+        for (taskid, status) in &tasks {
+            sender.send(Com::Msg(format!("    - {}: {}\n", taskid, status.status)))?;
+        }
+        Ok(())
     }
 
     fn send_task(sender: &Sender<Com>, name: &str, task: &Task) -> SenderResult {
-        sender.send(Com::Msg(format!("Info {}:\n", name)))?;
+        sender.send(Com::Msg(format!("Info {name}:\n")))?;
         sender.send(Com::Msg(task.to_string()))
     }
 
     fn send_error(sender: &Sender<Com>, message: String) -> SenderResult {
-        sender.send(Com::Msg(message))
+        sender.send(Com::Msg(format!("error: {message}")))
+    }
+
+    fn send_reload_diff(sender: &Sender<Com>, diff: &ReloadDiff) -> SenderResult {
+        if diff.is_empty() {
+            return sender.send(Com::Msg(String::from("reload would change nothing\n")));
+        }
+        sender.send(Com::Msg(String::from("reload would:\n")))?;
+        for name in &diff.added {
+            sender.send(Com::Msg(format!("    add {name}\n")))?;
+        }
+        for name in &diff.removed {
+            sender.send(Com::Msg(format!("    remove {name}\n")))?;
+        }
+        for (name, changes) in &diff.reloaded {
+            sender.send(Com::Msg(format!("    reload {name}\n")))?;
+            for change in changes {
+                sender.send(Com::Msg(format!("        {change}\n")))?;
+            }
+        }
+        for name in &diff.unchanged {
+            sender.send(Com::Msg(format!("    keep {name}\n")))?;
+        }
+        Ok(())
+    }
+
+    fn format_line(_name: &str, line: &LogLine, prefixed: bool) -> String {
+        if prefixed {
+            format!("[{}] {}\n", line.instance, line.line)
+        } else {
+            format!("{}\n", line.line)
+        }
+    }
+
+    fn format_tagged_line(name: &str, line: &LogLine, prefixed: bool) -> String {
+        if prefixed {
+            format!("[{}:{}] {}\n", name, line.instance, line.line)
+        } else {
+            format!("[{}] {}\n", name, line.line)
+        }
     }
 }
 
@@ -119,9 +280,24 @@ impl Formatter for Json {
         sender.send(Com::Msg(raw_msg))
     }
 
-    fn send_status(sender: &Sender<Com>, name: &str, status: Status) -> SenderResult {
+    fn send_reload_diff(sender: &Sender<Com>, diff: &ReloadDiff) -> SenderResult {
+        let raw_msg = serde_json::to_string(&Message::Diff(diff.clone())).unwrap();
+        sender.send(Com::Msg(raw_msg))
+    }
+
+    fn send_status(sender: &Sender<Com>, name: &str, status: &TaskStatus) -> SenderResult {
         let raw_msg =
-            serde_json::to_string(&Message::from_status(name.to_string(), status)).unwrap();
+            serde_json::to_string(&Message::from_status(name.to_string(), status.clone())).unwrap();
+        sender.send(Com::Msg(raw_msg))
+    }
+
+    fn send_statuses(
+        sender: &Sender<Com>,
+        selector: &str,
+        tasks: BTreeMap<String, TaskStatus>,
+    ) -> SenderResult {
+        let raw_msg =
+            serde_json::to_string(&Message::from_selection(selector.to_string(), tasks)).unwrap();
         sender.send(Com::Msg(raw_msg))
     }
 
@@ -138,6 +314,13 @@ impl Formatter for Json {
         let raw_msg = serde_json::to_string(&Message::from_tasks_iter(tasks)).unwrap();
         sender.send(Com::Msg(raw_msg))
     }
+
+    // one object per line, so a follow can be read as a stream
+    fn format_line(name: &str, line: &LogLine, _prefixed: bool) -> String {
+        let raw_msg =
+            serde_json::to_string(&Message::from_line(name.to_string(), line.clone())).unwrap();
+        raw_msg + "\n"
+    }
 }
 
 pub struct Yaml;
@@ -148,9 +331,24 @@ impl Formatter for Yaml {
         sender.send(Com::Msg(raw_msg))
     }
 
-    fn send_status(sender: &Sender<Com>, name: &str, status: Status) -> SenderResult {
+    fn send_reload_diff(sender: &Sender<Com>, diff: &ReloadDiff) -> SenderResult {
+        let raw_msg = serde_yaml::to_string(&Message::Diff(diff.clone())).unwrap();
+        sender.send(Com::Msg(raw_msg))
+    }
+
+    fn send_status(sender: &Sender<Com>, name: &str, status: &TaskStatus) -> SenderResult {
         let raw_msg =
-            serde_yaml::to_string(&Message::from_status(name.to_string(), status)).unwrap();
+            serde_yaml::to_string(&Message::from_status(name.to_string(), status.clone())).unwrap();
+        sender.send(Com::Msg(raw_msg))
+    }
+
+    fn send_statuses(
+        sender: &Sender<Com>,
+        selector: &str,
+        tasks: BTreeMap<String, TaskStatus>,
+    ) -> SenderResult {
+        let raw_msg =
+            serde_yaml::to_string(&Message::from_selection(selector.to_string(), tasks)).unwrap();
         sender.send(Com::Msg(raw_msg))
     }
 
@@ -167,4 +365,8 @@ impl Formatter for Yaml {
         let raw_msg = serde_yaml::to_string(&Message::from_tasks_iter(tasks)).unwrap();
         sender.send(Com::Msg(raw_msg))
     }
+
+    fn format_line(name: &str, line: &LogLine, _prefixed: bool) -> String {
+        serde_yaml::to_string(&Message::from_line(name.to_string(), line.clone())).unwrap()
+    }
 }
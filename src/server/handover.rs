@@ -0,0 +1,27 @@
+use std::{env, io, os::unix::process::CommandExt, path::Path, process::Command};
+
+// Set for a server replacing the previous one in the same process
+const HANDOVER: &str = "TASKMASTER_HANDOVER";
+
+// handed_over tell whether the server took over from a previous one, the
+// variable is removed so the tasks don't inherit it
+pub fn handed_over() -> bool {
+    let handed_over = env::var_os(HANDOVER).is_some();
+
+    env::remove_var(HANDOVER);
+    handed_over
+}
+
+// exec replace the server by the binary at exe, run with the same arguments:
+// the children stay children of the process and their pipes are inherited,
+// it only returns on failure
+pub fn exec(exe: &Path) -> io::Error {
+    let mut args = env::args_os();
+    let mut command = Command::new(exe);
+
+    if let Some(arg0) = args.next() {
+        command.arg0(arg0);
+    }
+    log::info!("handing over to {}", exe.display());
+    command.args(args).env(HANDOVER, "1").exec()
+}
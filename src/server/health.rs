@@ -0,0 +1,474 @@
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+    io::{self, prelude::*},
+    net::{TcpStream, ToSocketAddrs},
+    os::unix::process::CommandExt,
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    thread, time,
+};
+
+use super::{default, inter::Inter};
+
+// Probe is what is checked: a command exiting with 0, a port accepting
+// connections or an url answering with a 2xx or 3xx status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Probe {
+    Exec(String),
+    Tcp(String),
+    Http(HttpUrl),
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Probe::Exec(cmd) => write!(f, "exec {cmd:?}"),
+            Probe::Tcp(address) => write!(f, "tcp {address}"),
+            Probe::Http(url) => write!(f, "http {url}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheck {
+    #[serde(flatten)]
+    pub probe: Probe,
+
+    #[serde(default = "default::health_interval")]
+    pub interval: u32,
+
+    #[serde(default = "default::health_timeout")]
+    pub timeout: u32,
+
+    // consecutive failures before the task is unhealthy
+    #[serde(default = "default::health_retries")]
+    pub retries: u32,
+
+    #[serde(default)]
+    pub restart: bool,
+}
+
+impl Display for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} every {}s, timeout {}s, {} retries, restart: {}",
+            self.probe, self.interval, self.timeout, self.retries, self.restart
+        )
+    }
+}
+
+// Only plain http is supported, which is enough for a local endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HttpUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl TryFrom<String> for HttpUrl {
+    type Error = String;
+
+    fn try_from(url: String) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("{url}: only http:// urls are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("{url}: invalid port {port:?}"))?,
+            ),
+            None => (authority, 80),
+        };
+
+        if host.is_empty() {
+            return Err(format!("{url}: missing host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl From<HttpUrl> for String {
+    fn from(url: HttpUrl) -> String {
+        url.to_string()
+    }
+}
+
+impl Display for HttpUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+type CheckResult<T = ()> = Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthStatus {
+    pub failures: u32,
+    pub retries: u32,
+    pub last_error: Option<String>,
+}
+
+impl Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.last_error {
+            Some(e) if self.failures > 0 => {
+                write!(f, "{}/{} checks failed: {}", self.failures, self.retries, e)
+            }
+            _ => write!(f, "healthy"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Attempt {
+    // exec probes are children of the server, their status comes from the reaper
+    Process(Pid),
+    Thread(Receiver<CheckResult>),
+}
+
+#[derive(Debug)]
+struct InFlight {
+    attempt: Attempt,
+    deadline: time::Instant,
+}
+
+// Checker run the health check of a task at every interval, one at a time
+#[derive(Debug)]
+pub struct Checker {
+    config: HealthCheck,
+    next: time::Instant,
+    failures: u32,
+    // a check passed since the last reset
+    passed: bool,
+    last_error: Option<String>,
+    inflight: Option<InFlight>,
+}
+
+impl Checker {
+    pub fn new(config: HealthCheck) -> Self {
+        Self {
+            next: time::Instant::now() + interval(&config),
+            config,
+            failures: 0,
+            passed: false,
+            last_error: None,
+            inflight: None,
+        }
+    }
+
+    pub fn config(&self) -> &HealthCheck {
+        &self.config
+    }
+
+    // reset cancel the running check and wait a whole interval before the next one
+    pub fn reset(&mut self) {
+        self.cancel();
+        self.failures = 0;
+        self.passed = false;
+        self.last_error = None;
+        self.next = time::Instant::now() + interval(&self.config);
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(InFlight {
+            attempt: Attempt::Process(pid),
+            ..
+        }) = self.inflight.take()
+        {
+            if let Err(e) = kill(Pid::from_raw(-pid.as_raw()), Signal::SIGKILL) {
+                log::debug!("cannot kill health check {pid}: {e}");
+            }
+        }
+    }
+
+    pub fn deadline(&self) -> time::Instant {
+        self.inflight
+            .as_ref()
+            .map_or(self.next, |inflight| inflight.deadline)
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.passed
+    }
+
+    pub fn is_unhealthy(&self) -> bool {
+        self.failures >= self.config.retries.max(1)
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        HealthStatus {
+            failures: self.failures,
+            retries: self.config.retries,
+            last_error: self.last_error.clone(),
+        }
+    }
+
+    // poll start the check when due, and return the result of the running one once
+    // known, a check thread wakes the server up through event when it is done
+    pub fn poll(&mut self, now: time::Instant, event: &Sender<Inter>) -> Option<CheckResult> {
+        match &self.inflight {
+            None if now >= self.next => {
+                self.inflight = Some(self.start(now, event));
+                None
+            }
+            None => None,
+            Some(inflight) => {
+                let result = match &inflight.attempt {
+                    Attempt::Thread(receiver) => match receiver.try_recv() {
+                        Ok(result) => Some(result),
+                        Err(TryRecvError::Disconnected) => Some(Err(String::from("check aborted"))),
+                        Err(TryRecvError::Empty) => None,
+                    },
+                    Attempt::Process(_) => None,
+                };
+                match result {
+                    Some(result) => Some(self.record(result)),
+                    None if now >= inflight.deadline => {
+                        self.cancel();
+                        Some(self.record(Err(String::from("timed out"))))
+                    }
+                    None => None,
+                }
+            }
+        }
+    }
+
+    // exited return the result of an exec check, if pid is the one running
+    pub fn exited(&mut self, pid: Pid, status: ExitStatus) -> Option<CheckResult> {
+        match self.inflight {
+            Some(InFlight {
+                attempt: Attempt::Process(running),
+                ..
+            }) if running == pid => {
+                self.inflight = None;
+                let result = if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("command exited with {status}"))
+                };
+                Some(self.record(result))
+            }
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, result: CheckResult) -> CheckResult {
+        self.inflight = None;
+        self.next = time::Instant::now() + interval(&self.config);
+        match &result {
+            Ok(()) => {
+                self.failures = 0;
+                self.passed = true;
+                self.last_error = None;
+            }
+            Err(e) => {
+                self.failures += 1;
+                self.last_error = Some(e.clone());
+            }
+        }
+        result
+    }
+
+    fn start(&self, now: time::Instant, event: &Sender<Inter>) -> InFlight {
+        let timeout = time::Duration::from_secs(self.config.timeout.max(1).into());
+        let attempt = match &self.config.probe {
+            Probe::Exec(cmd) => match spawn(cmd) {
+                Ok(pid) => Attempt::Process(pid),
+                Err(e) => in_thread(event, move || Err(format!("cannot run command: {e}"))),
+            },
+            Probe::Tcp(address) => {
+                let address = address.clone();
+                in_thread(event, move || connect(&address, timeout).map(drop))
+            }
+            Probe::Http(url) => {
+                let url = url.clone();
+                in_thread(event, move || get(&url, timeout))
+            }
+        };
+
+        InFlight {
+            attempt,
+            deadline: now + timeout,
+        }
+    }
+}
+
+fn interval(config: &HealthCheck) -> time::Duration {
+    time::Duration::from_secs(config.interval.max(1).into())
+}
+
+fn in_thread(
+    event: &Sender<Inter>,
+    check: impl FnOnce() -> CheckResult + Send + 'static,
+) -> Attempt {
+    let (sender, receiver) = channel();
+    let event = event.clone();
+
+    thread::spawn(move || {
+        if sender.send(check()).is_ok() {
+            drop(event.send(Inter::Checked));
+        }
+    });
+    Attempt::Thread(receiver)
+}
+
+// the check is reaped by the server like any other child
+#[allow(clippy::cast_possible_wrap, clippy::zombie_processes)]
+fn spawn(cmd: &str) -> io::Result<Pid> {
+    let child = Command::new("/bin/sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+
+    Ok(Pid::from_raw(child.id() as i32))
+}
+
+fn connect(address: &str, timeout: time::Duration) -> CheckResult<TcpStream> {
+    let addresses = address
+        .to_socket_addrs()
+        .map_err(|e| format!("cannot resolve {address}: {e}"))?;
+    let mut last_error = format!("cannot resolve {address}");
+
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = format!("cannot connect to {address}: {e}"),
+        }
+    }
+    Err(last_error)
+}
+
+fn get(url: &HttpUrl, timeout: time::Duration) -> CheckResult {
+    let mut stream = connect(&format!("{}:{}", url.host, url.port), timeout)?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        url.path, url.host
+    );
+    let mut response = String::new();
+
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|()| stream.write_all(request.as_bytes()))
+        .and_then(|()| (&mut stream).take(1024).read_to_string(&mut response))
+        .map_err(|e| format!("{url}: {e}"))?;
+    check_response(&response).map_err(|e| format!("{url}: {e}"))
+}
+
+fn check_response(response: &str) -> CheckResult {
+    let status = response
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| String::from("invalid response"))?;
+
+    if (200..400).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("status {status}"))
+    }
+}
+
+#[cfg(test)]
+mod test_health {
+    use super::{check_response, Checker, HealthCheck, HttpUrl, Probe};
+    use crate::server::inter::Inter;
+    use std::{convert::TryFrom, net::TcpListener, sync::mpsc::channel, time};
+
+    fn url(url: &str) -> Result<HttpUrl, String> {
+        HttpUrl::try_from(String::from(url))
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            url("http://localhost:8080/health").unwrap().to_string(),
+            "http://localhost:8080/health"
+        );
+        assert_eq!(
+            url("http://127.0.0.1").unwrap().to_string(),
+            "http://127.0.0.1:80/"
+        );
+        assert!(url("https://localhost/").is_err());
+        assert!(url("http://localhost:port/").is_err());
+        assert!(url("http://:80/").is_err());
+    }
+
+    #[test]
+    fn test_parse_healthcheck() {
+        let check: HealthCheck =
+            serde_yaml::from_str("tcp: 127.0.0.1:5432\ninterval: 5\nrestart: true\n").unwrap();
+        assert_eq!(check.probe, Probe::Tcp(String::from("127.0.0.1:5432")));
+        assert_eq!((check.interval, check.timeout, check.retries), (5, 2, 3));
+        assert!(check.restart);
+
+        assert!(serde_yaml::from_str::<HealthCheck>("http: ftp://localhost\n").is_err());
+    }
+
+    #[test]
+    fn test_check_response() {
+        assert!(check_response("HTTP/1.1 200 OK\r\n\r\n").is_ok());
+        assert!(check_response("HTTP/1.0 302 Found\r\n").is_ok());
+        assert_eq!(
+            check_response("HTTP/1.1 503 Service Unavailable\r\n"),
+            Err(String::from("status 503"))
+        );
+        assert!(check_response("").is_err());
+    }
+
+    #[test]
+    fn test_tcp_check() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut checker = Checker::new(HealthCheck {
+            probe: Probe::Tcp(address),
+            interval: 1,
+            timeout: 1,
+            retries: 1,
+            restart: false,
+        });
+        let (event, woken) = channel();
+        let now = time::Instant::now() + time::Duration::from_secs(1);
+
+        // the result is there as soon as the thread wakes the server up,
+        // long before the check times out
+        assert_eq!(checker.poll(now, &event), None);
+        assert!(matches!(
+            woken.recv_timeout(time::Duration::from_millis(500)),
+            Ok(Inter::Checked)
+        ));
+        assert_eq!(checker.poll(now, &event), Some(Ok(())));
+
+        drop(listener);
+        checker.next = now;
+        assert_eq!(checker.poll(now, &event), None);
+        assert!(matches!(
+            woken.recv_timeout(time::Duration::from_millis(500)),
+            Ok(Inter::Checked)
+        ));
+        assert!(checker.poll(now, &event).unwrap().is_err());
+        assert!(checker.is_unhealthy());
+    }
+}
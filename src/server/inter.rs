@@ -1,13 +1,28 @@
+use std::sync::mpsc::Sender;
+
+use super::{access::Peer, communication::Com};
 use crate::shared::message::Message;
 
 #[derive(Debug)]
 pub enum Inter {
-    // When we receive a message from the client
-    FromClient(Message),
+    // When we receive a message from the client, with the channel to answer on
+    FromClient(Message, Sender<Com>, Peer),
 
     // Server need to quit
     Quit,
 
+    // Server need to quit, leaving the tasks running
+    Release,
+
+    // Server need to execute itself again, handing the tasks over
+    Handover,
+
     // Reload the configuration file
     Reload,
+
+    // A child has terminated and need to be reaped
+    Reap,
+
+    // A health check thread has a result to be collected
+    Checked,
 }
@@ -1,39 +1,75 @@
-use std::sync::{Arc,Mutex};
+use nix::{
+    sys::{
+        socket::{getsockopt, sockopt::PeerCredentials},
+        stat::{self, Mode},
+    },
+    unistd::{Gid, Uid},
+};
 use serde::Deserialize;
-use std::fs;
+use std::fs::{self, Permissions};
 use std::io::prelude::*;
-use std::os::unix::net::{UnixListener, UnixStream};
-use std::sync::mpsc::{Sender, Receiver};
+use std::os::unix::{
+    fs::{chown, FileTypeExt, PermissionsExt},
+    io::AsRawFd,
+    net::{UnixListener, UnixStream},
+};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
 use std::thread;
 
-use super::{communication::Com, inter::Inter};
+use super::{access::Peer, communication::Com, error, inter::Inter};
 use crate::shared::message::Message;
 
+#[derive(Debug, Clone)]
+pub struct SocketConfig {
+    pub path: PathBuf,
+    pub mode: Option<u32>,
+    pub owner: Option<Uid>,
+    pub group: Option<Gid>,
+}
+
 pub struct Listener {
     pub sock: UnixListener,
-
+    path: PathBuf,
 }
 
 impl Listener {
-    pub fn new() -> Self {
-        Self {
-            sock: UnixListener::bind("/tmp/taskmaster.sock").unwrap(),
+    pub fn new(config: &SocketConfig) -> Result<Self, error::Taskmaster> {
+        remove_stale_socket(&config.path)?;
+
+        // nobody but us can connect until the permissions are set
+        let previous_umask = stat::umask(Mode::from_bits_truncate(0o177));
+        let sock = UnixListener::bind(&config.path);
+        stat::umask(previous_umask);
+
+        let listener = Self {
+            sock: sock?,
+            path: config.path.clone(),
+        };
+        if config.owner.is_some() || config.group.is_some() {
+            chown(
+                &config.path,
+                config.owner.map(Uid::as_raw),
+                config.group.map(Gid::as_raw),
+            )?;
+        }
+        if let Some(mode) = config.mode {
+            fs::set_permissions(&config.path, Permissions::from_mode(mode))?;
         }
+        Ok(listener)
     }
 
-    pub fn run(&mut self, sender: Sender<Inter>, receiver: Receiver<Com>) {
+    pub fn run(&mut self, sender: Sender<Inter>) {
         let listener = self.sock.try_clone().unwrap();
-        let reference = Arc::new(Mutex::new(receiver));
         thread::spawn(move || {
             for stream in listener.incoming() {
-                let copy = reference.clone();
                 match stream {
                     Ok(stream) => {
                         let s = sender.clone();
-                        thread::spawn(move || process_message(stream, &s, &copy));
+                        thread::spawn(move || process_message(stream, &s));
                     }
                     Err(err) => {
-                        log::error!("{}", err);
+                        log::error!("{err}");
                         break;
                     }
                 }
@@ -44,25 +80,110 @@ impl Listener {
 
 impl Drop for Listener {
     fn drop(&mut self) {
-        fs::remove_file("/tmp/taskmaster.sock").unwrap();
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::error!("cannot remove socket {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+// A socket file nobody is listening on is a leftover of a crashed server,
+// anything else at that path is left alone
+fn remove_stale_socket(path: &Path) -> Result<(), error::Taskmaster> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        log::error!("{} exists and is not a socket", path.display());
+        return Err(error::Taskmaster::NotASocket(path.display().to_string()));
+    }
+    if UnixStream::connect(path).is_ok() {
+        log::error!("a server is already listening on {}", path.display());
+        return Err(error::Taskmaster::SocketInUse);
     }
+    log::warn!("removing stale socket {}", path.display());
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+fn peer_credentials(stream: &UnixStream) -> Result<Peer, nix::Error> {
+    let credentials = getsockopt(stream.as_raw_fd(), PeerCredentials)?;
+
+    Ok(Peer {
+        uid: Uid::from_raw(credentials.uid()),
+        gid: Gid::from_raw(credentials.gid()),
+    })
 }
 
-fn process_message(stream: UnixStream, sender: &Sender<Inter>, receiver: &Arc<Mutex<Receiver<Com>>>) {
+// Each connection get its own response channel, so replies can't be mixed up
+fn process_message(stream: UnixStream, sender: &Sender<Inter>) {
     log::info!("Ready to recieve.");
+    let peer = match peer_credentials(&stream) {
+        Ok(peer) => peer,
+        Err(e) => {
+            log::error!("cannot get peer credentials, closing connection: {e}");
+            return;
+        }
+    };
     let mut response = stream.try_clone().expect("Couldn't clone socket");
     let mut de = serde_json::Deserializer::from_reader(stream);
-    let re = receiver.lock().unwrap();
+    let (reply, receiver) = channel::<Com>();
 
     if let Ok(msg) = Message::deserialize(&mut de) {
-        log::info!("Recieved {:?}", msg);
-        sender.send(Inter::FromClient(msg)).unwrap();
-        for res in re.iter() {
+        log::info!("Recieved {msg:?} from {peer:?}");
+        // a follow only end when the client leave
+        let streaming = matches!(msg, Message::Follow { .. });
+        sender.send(Inter::FromClient(msg, reply, peer)).unwrap();
+        let mut connected = true;
+        // keep draining until the end so the server never write to a closed channel
+        for res in &receiver {
             match res {
-                Com::Msg(data) => response.write_all(data.as_bytes()).unwrap(),
-                Com::End => break
+                Com::Msg(data) if connected => {
+                    if let Err(e) = response.write_all(data.as_bytes()) {
+                        log::warn!("client went away: {e}");
+                        if streaming {
+                            break;
+                        }
+                        connected = false;
+                    }
+                }
+                Com::Msg(_) => {}
+                Com::End => break,
             }
         }
     }
     log::info!("End of transmission.");
 }
+
+#[cfg(test)]
+mod test_listener {
+    use super::remove_stale_socket;
+    use crate::shared::error;
+    use std::{env, fs, os::unix::net::UnixListener, process};
+
+    #[test]
+    fn test_remove_stale_socket() {
+        let dir = env::temp_dir().join(format!("taskmaster-listener-{}", process::id()));
+        let file = dir.join("config.yml");
+        let socket = dir.join("taskmaster.sock");
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(&file, "keep me").unwrap();
+        assert!(matches!(
+            remove_stale_socket(&file),
+            Err(error::Taskmaster::NotASocket(_))
+        ));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+
+        let listener = UnixListener::bind(&socket).unwrap();
+        assert!(matches!(
+            remove_stale_socket(&socket),
+            Err(error::Taskmaster::SocketInUse)
+        ));
+        drop(listener);
+        assert!(remove_stale_socket(&socket).is_ok());
+        assert!(!socket.exists());
+        assert!(remove_stale_socket(&socket).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,30 +1,50 @@
 use std::{
     convert::TryFrom,
-    marker,
+    env, marker,
+    path::PathBuf,
     str::FromStr,
-    sync::mpsc::{channel, Sender},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    time,
 };
 
+mod access;
+mod backoff;
+mod check;
 mod communication;
 mod default;
+mod dependency;
+mod diff;
 mod formatter;
+mod handover;
+mod health;
 mod inter;
 mod listener;
 mod monitor;
 mod nix_utils;
+mod output;
+mod persist;
+mod reaper;
 mod relaunch;
+mod schedule;
+mod selector;
 mod signal;
 mod state;
+mod stop;
+mod tail;
 mod task;
 mod watcher;
 
 use crate::shared::{error, message::Message};
 
+pub use self::{check::check, handover::handed_over, listener::SocketConfig, watcher::WatchConfig};
+
 use self::{
+    access::Peer,
     communication::Com,
     formatter::{Formatter, Human, Json, MessageFormat, Yaml},
     inter::Inter,
     listener::Listener,
+    persist::StateFile,
     state::State,
     watcher::Watcher,
 };
@@ -34,73 +54,191 @@ where
     F: Formatter,
 {
     state: State<F>,
+    watcher: Watcher,
     event: Sender<Inter>,
     _marker: marker::PhantomData<F>,
 }
 
-pub fn start(config: &str, format: &str) -> Result<(), error::Taskmaster> {
-    log::info!("starting server with config file {}", config,);
-    log::info!("message output format as {}", format);
+pub fn start(
+    config: &str,
+    format: &str,
+    socket: &SocketConfig,
+    watch: WatchConfig,
+    state_file: Option<PathBuf>,
+    handed_over: bool,
+    ready: impl FnOnce(),
+) -> Result<(), error::Taskmaster> {
+    log::info!("starting server with config file {config}");
+    log::info!("message output format as {format}");
+    log::info!("listening on {}", socket.path.display());
     let format = MessageFormat::from_str(format).unwrap();
+    let persisted = state_file.map(StateFile::new);
     match format {
-        MessageFormat::Human => start_raw::<Human>(config),
-        MessageFormat::Yaml => start_raw::<Yaml>(config),
-        MessageFormat::Json => start_raw::<Json>(config),
+        MessageFormat::Human => {
+            start_raw::<Human>(config, socket, watch, persisted, handed_over, ready)
+        }
+        MessageFormat::Yaml => {
+            start_raw::<Yaml>(config, socket, watch, persisted, handed_over, ready)
+        }
+        MessageFormat::Json => {
+            start_raw::<Json>(config, socket, watch, persisted, handed_over, ready)
+        }
     }
 }
 
-pub fn start_raw<F: Formatter>(config: &str) -> Result<(), error::Taskmaster> {
+pub fn start_raw<F: Formatter>(
+    config: &str,
+    socket: &SocketConfig,
+    watch: WatchConfig,
+    persisted: Option<StateFile>,
+    handed_over: bool,
+    ready: impl FnOnce(),
+) -> Result<(), error::Taskmaster> {
     let (sender, event) = channel::<Inter>();
-    let (response, receiver) = channel::<Com>();
+    // found before an upgrade replaces the binary, for a handover to run the new one
+    let exe = env::current_exe()?;
+
+    let watcher = Watcher::try_from(config)?.configure(watch);
+    let mut listener = Listener::new(socket)?;
 
-    let mut watcher = Watcher::try_from(config)?;
-    let mut listener = Listener::new();
+    // orphaned descendants of the tasks are reparented to the server, which reaps them
+    if let Err(e) = nix_utils::set_child_subreaper() {
+        log::warn!("cannot become a child subreaper: {e}");
+    }
     let mut server = Server {
-        state: State::<F>::new(sender.clone(), response.clone()),
+        state: State::<F>::new(sender.clone(), persisted, handed_over),
+        watcher,
         event: sender.clone(),
         _marker: marker::PhantomData,
     };
 
-    watcher.run(sender.clone());
-    listener.run(sender.clone(), receiver);
+    // the server is only ready once its tasks are loaded, a daemon fails otherwise
+    server.state.reload(&server.watcher)?;
+    server.watcher.run(sender.clone());
+    listener.run(sender.clone());
 
     signal::handle_signals(sender)?;
+    // the children handed over may have exited unnoticed during the exec
+    if handed_over {
+        server.state.reap();
+    }
+    ready();
     loop {
-        if let Ok(message) = event.recv() {
-            log::info!("received internal message: {:?}", message);
-            match message {
-                Inter::FromClient(msg) => {
-                    server.handle_client_message(msg);
-                    response.send(Com::End).unwrap();
+        match next_event(&event, server.state.next_deadline()) {
+            Ok(message) => {
+                log::info!("received internal message: {message:?}");
+                match message {
+                    Inter::FromClient(msg, response, peer) => {
+                        if !server.handle_client_message(msg, &response, peer) {
+                            drop(response.send(Com::End));
+                        }
+                    }
+                    Inter::Reload => drop(server.reload_config()),
+                    Inter::Reap => server.state.reap(),
+                    // collected by the cycle below
+                    Inter::Checked => {}
+                    Inter::Quit if server.state.is_shutting_down() => break,
+                    Inter::Quit => server.state.shutdown(),
+                    Inter::Release => {
+                        server.state.release();
+                        break;
+                    }
+                    Inter::Handover => {
+                        server.state.release();
+                        let e = handover::exec(&exe);
+                        log::error!("cannot hand over, taking the tasks back: {e}");
+                        server.state.take_back();
+                    }
                 }
-                Inter::Reload => server.reload_config(&watcher),
-                Inter::Quit => break,
             }
-        };
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        server.state.cycle();
+        server.state.persist();
+        if server.state.is_shut_down() {
+            break;
+        }
     }
     Ok(())
 }
 
+// next_event wait for an event, or until the next monitor deadline is reached
+fn next_event(
+    event: &Receiver<Inter>,
+    deadline: Option<time::Instant>,
+) -> Result<Inter, RecvTimeoutError> {
+    match deadline {
+        Some(deadline) => {
+            event.recv_timeout(deadline.saturating_duration_since(time::Instant::now()))
+        }
+        None => event.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    }
+}
+
 impl<F: Formatter> Server<F> {
-    fn reload_config(&mut self, watcher: &Watcher) {
-        self.state.reload(watcher)
+    // On failure the previous tasks are kept untouched
+    fn reload_config(&mut self) -> Result<(), error::Taskmaster> {
+        self.state.reload(&self.watcher).map_err(|e| {
+            log::error!("unable to reload config, keeping previous tasks: {e}");
+            e
+        })
     }
 
-    fn handle_client_message(&mut self, message: Message) {
+    // return true when the connection is kept open to stream task output
+    fn handle_client_message(
+        &mut self,
+        message: Message,
+        response: &Sender<Com>,
+        peer: Peer,
+    ) -> bool {
+        if !self.state.is_allowed(peer, &message) {
+            State::<F>::permission_denied(response, peer, &message);
+            return false;
+        }
         match message {
-            Message::Reload => self.event.send(Inter::Reload).unwrap(),
-            Message::Start { id: taskname } => self.state.start(&taskname),
-            Message::Info { id: taskname } => self.state.info(&taskname),
-            Message::Stop { id: taskname } => self.state.stop(&taskname),
-            Message::List => self.state.list(),
-            Message::Status { id: taskname } => self.state.status(&taskname),
+            Message::Reload { dry_run: true } => self.state.reload_diff(response, &self.watcher),
+            Message::Reload { dry_run: false } => {
+                if let Err(e) = self.reload_config() {
+                    State::<F>::reload_failed(response, &e);
+                }
+            }
+            Message::Start { id: taskname } => self.state.start(response, &taskname),
+            Message::Info { id: taskname } => self.state.info(response, &taskname),
+            Message::Stop { id: taskname } => self.state.stop(response, &taskname),
+            Message::List => self.state.list(response),
+            Message::Status { id: taskname } => self.state.status(response, &taskname),
             Message::Restart { id: taskname } => {
-                self.state.restart(&taskname);
+                self.state.restart(response, &taskname);
             }
-            Message::Quit => self
+            Message::Pause { id: taskname } => self.state.pause(response, &taskname),
+            Message::Resume { id: taskname } => self.state.resume(response, &taskname),
+            Message::Tail {
+                id: taskname,
+                lines,
+            } => self.state.tail(response, &taskname, lines),
+            Message::Follow { id: taskname } => return self.state.follow(response, &taskname),
+            Message::Signal {
+                id: taskname,
+                signal,
+                instance,
+            } => self.state.signal(response, &taskname, &signal, instance),
+            Message::Handover if !self.state.can_release() => {
+                State::<F>::handover_refused(response);
+            }
+            Message::Handover => self
+                .event
+                .send(Inter::Handover)
+                .expect("cannot send handover message"),
+            Message::Quit { keep_children } => self
                 .event
-                .send(Inter::Quit)
+                .send(if keep_children {
+                    Inter::Release
+                } else {
+                    Inter::Quit
+                })
                 .expect("cannot send quit message"),
-        };
+        }
+        false
     }
 }
@@ -1,6 +1,11 @@
 use super::{
+    health::{Checker, HealthStatus},
     inter::Inter,
+    output::{OutputConfig, OutputMode, Outputs, Sink},
+    persist::{self, SavedChild, SavedMonitor},
     relaunch::Relaunch,
+    schedule::{Missed, Overlap},
+    tail::{SharedTail, Stream, Tail},
     task::{get_current_timestamp, Task},
 };
 use nix::{
@@ -10,12 +15,20 @@ use nix::{
 };
 use serde::Serialize;
 use std::{
+    convert::TryFrom,
     fmt::{self, Debug, Display, Formatter},
-    process::{Child, Command, ExitStatus},
-    sync::mpsc::Sender,
+    fs::File,
+    mem,
+    os::unix::{io::OwnedFd, process::ExitStatusExt},
+    process::{Command, ExitStatus, Stdio},
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread::JoinHandle,
     time,
 };
 
+// How often adopted children, which can't be reaped, are checked for
+const ADOPTED_POLL: time::Duration = time::Duration::from_secs(1);
+
 #[derive(Copy, Clone, Serialize, PartialEq, Debug)]
 pub enum Status {
     Inactive,
@@ -27,6 +40,13 @@ pub enum Status {
     Failed,
     Stopping,
     Stopped,
+    Backoff,
+    Fatal,
+    Waiting,
+    Unhealthy,
+    Paused,
+    // only reported, for an active task with children not up for their successdelay yet
+    Starting,
 }
 
 impl Display for Status {
@@ -41,117 +61,400 @@ impl Display for Status {
             Status::Failed => "failed",
             Status::Stopping => "stopping",
             Status::Stopped => "stopped",
+            Status::Backoff => "backoff",
+            Status::Fatal => "fatal",
+            Status::Waiting => "waiting",
+            Status::Unhealthy => "unhealthy",
+            Status::Paused => "paused",
+            Status::Starting => "starting",
         };
-        write!(f, "{}", s)
+        write!(f, "{s}")
     }
 }
 
+// Why a finished child was classified as failed
+#[derive(Copy, Clone, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Failure {
+    Signaled,
+    UnexpectedExitCode,
+    ExitedTooEarly,
+    Vanished,
+    TimedOut,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Failure::Signaled => "killed by a signal",
+            Failure::UnexpectedExitCode => "unexpected exit code",
+            Failure::ExitedTooEarly => "exited before successdelay",
+            Failure::Vanished => "adopted child vanished",
+            Failure::TimedOut => "timed out",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Clone, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Exit {
+    Code(i32),
+    Signal(String),
+    Unknown,
+}
+
+impl From<ExitStatus> for Exit {
+    fn from(status: ExitStatus) -> Self {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => Exit::Code(code),
+            (None, Some(signum)) => Exit::Signal(
+                Signal::try_from(signum).map_or_else(|_| signum.to_string(), |sig| sig.to_string()),
+            ),
+            (None, None) => Exit::Signal(String::from("unknown")),
+        }
+    }
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Exit::Code(code) => write!(f, "exit code {code}"),
+            Exit::Signal(signal) => write!(f, "killed by {signal}"),
+            Exit::Unknown => write!(f, "unknown exit status"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ChildState {
+    Running,
+    Paused,
+    Stopping,
+}
+
+impl Display for ChildState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChildState::Running => write!(f, "running"),
+            ChildState::Paused => write!(f, "paused"),
+            ChildState::Stopping => write!(f, "stopping"),
+        }
+    }
+}
+
+// started_at is a unix timestamp, uptime is in seconds
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct ChildStatus {
+    pub instance: u32,
+    pub pid: i32,
+    pub state: ChildState,
+    pub started_at: u64,
+    pub uptime: u64,
+}
+
+impl Display for ChildStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "instance {}: pid {}, {}, started at {}, up {}s",
+            self.instance, self.pid, self.state, self.started_at, self.uptime
+        )
+    }
+}
+
+// runtime is in seconds
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct LastExit {
+    pub instance: u32,
+    pub pid: i32,
+    pub exit: Exit,
+    pub runtime: u64,
+    pub failure: Option<Failure>,
+}
+
+impl Display for LastExit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "instance {}: pid {}, {} after {}s",
+            self.instance, self.pid, self.exit, self.runtime
+        )?;
+        if let Some(failure) = self.failure {
+            write!(f, ", failed: {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct TaskStatus {
+    pub status: Status,
+    pub retry_count: u32,
+    pub children: Vec<ChildStatus>,
+    pub last_exit: Option<LastExit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthStatus>,
+    // unix timestamp of the next scheduled run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_run: Option<u64>,
+}
+
 #[derive(Debug)]
 struct RunningChild {
-    child: Child,
+    pid: Pid,
+    instance: u32,
 
     started_at: time::Instant,
+    started_on: time::Duration,
     startup_time: time::Duration,
 
-    stopsignal: Signal,
-    stopdelay: time::Duration,
+    // the signals sent in turn to stop the child, with the delay each one is given
+    stop_steps: Vec<(Signal, time::Duration)>,
+    stopasgroup: bool,
+    killasgroup: bool,
+    timeout: Option<time::Duration>,
+
+    // the time spent paused doesn't count in the successdelay nor the timeout
+    paused_at: Option<time::Instant>,
+    paused: time::Duration,
+
+    // an adopted child was spawned by a previous server, it can only be polled
+    adopted: bool,
+
+    // the threads forwarding the outputs, which give the pipes back on a handover
+    pipes: Vec<(Stream, JoinHandle<Option<OwnedFd>>)>,
 }
 
 impl RunningChild {
-    fn new(
-        child: Child,
-        started_at: time::Instant,
-        startup_time: time::Duration,
-        stopsignal: Signal,
-        stopdelay: time::Duration,
-    ) -> Self {
+    fn new(pid: Pid, instance: u32, started_on: time::Duration, task: &Task) -> Self {
         Self {
-            child,
-            started_at,
-            startup_time,
-            stopsignal,
-            stopdelay,
+            pid,
+            instance,
+            started_at: time::Instant::now(),
+            started_on,
+            startup_time: time::Duration::from_secs(task.successdelay.into()),
+            stop_steps: task.stopsignal.steps(task.stopdelay),
+            stopasgroup: task.stopasgroup,
+            killasgroup: task.killasgroup,
+            timeout: (task.timeout > 0).then(|| time::Duration::from_secs(task.timeout.into())),
+            paused_at: None,
+            paused: time::Duration::ZERO,
+            adopted: false,
+            pipes: Vec::new(),
         }
     }
 
-    fn try_wait(&mut self) -> Result<Option<ExitStatus>, std::io::Error> {
-        self.child.try_wait()
+    // a child handed over is still a child of the server, in a new program
+    fn adopt(saved: &SavedChild, task: &Task, handed_over: bool) -> Self {
+        let started_on = time::Duration::from_secs(saved.started_on);
+        let uptime = get_current_timestamp().saturating_sub(started_on);
+        let mut child = Self::new(Pid::from_raw(saved.pid), saved.instance, started_on, task);
+
+        child.started_at = time::Instant::now()
+            .checked_sub(uptime)
+            .unwrap_or(child.started_at);
+        child.adopted = !handed_over;
+        child
     }
 
-    #[allow(clippy::cast_possible_wrap)]
-    fn stop(self) -> Result<StoppingChild, nix::Error> {
-        let pid = self.child.id() as i32;
-
-        kill(Pid::from_raw(pid), self.stopsignal)?;
-        Ok(StoppingChild::new(
-            self.child,
-            self.started_at,
-            self.startup_time,
-            time::Instant::now(),
-            self.stopdelay,
-        ))
+    fn saved(&self) -> SavedChild {
+        SavedChild::new(self.pid, self.instance, self.started_on.as_secs())
     }
 
-    fn kill(&mut self) -> std::io::Result<()> {
-        self.child.kill()
+    // hand_over take the output pipes back from the stopped forwarding threads,
+    // for the next server to inherit them
+    fn hand_over(&mut self, taskid: &str) -> SavedChild {
+        let mut saved = self.saved();
+
+        for (stream, forwarding) in self.pipes.drain(..) {
+            // the pipe is closed already
+            let Ok(Some(pipe)) = forwarding.join() else {
+                continue;
+            };
+            match persist::handed_over(pipe) {
+                Ok(fd) if stream == Stream::Stdout => saved.stdout = Some(fd),
+                Ok(fd) => saved.stderr = Some(fd),
+                Err(e) => log::error!(
+                    "[{}] cannot hand over the output of child-{}: {}",
+                    taskid,
+                    self.pid,
+                    e
+                ),
+            }
+        }
+        saved
+    }
+
+    // target is the child alone, or its whole process group
+    fn target(&self, group: bool) -> Pid {
+        if group {
+            Pid::from_raw(-self.pid.as_raw())
+        } else {
+            self.pid
+        }
+    }
+
+    // send_step send the signal of a stop step, there is at least one
+    fn send_step(&self, step: usize) -> Result<Signal, nix::Error> {
+        let (signal, _) = self.stop_steps[step];
+
+        kill(self.target(self.stopasgroup), signal)?;
+        Ok(signal)
+    }
+
+    // timeout_at is when the child will have run for too long
+    fn timeout_at(&self) -> Option<time::Instant> {
+        self.timeout
+            .map(|timeout| self.started_at + timeout + self.paused_for())
+    }
+
+    fn paused_for(&self) -> time::Duration {
+        self.paused
+            + self
+                .paused_at
+                .map_or(time::Duration::ZERO, |at| at.elapsed())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    // pause freeze the child, with its group when it is stopped as a group
+    fn pause(&mut self) -> Result<(), nix::Error> {
+        kill(self.target(self.stopasgroup), Signal::SIGSTOP)?;
+        self.paused_at.get_or_insert_with(time::Instant::now);
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), nix::Error> {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused += paused_at.elapsed();
+        }
+        kill(self.target(self.stopasgroup), Signal::SIGCONT)
+    }
+
+    fn status(&self, state: ChildState) -> ChildStatus {
+        ChildStatus {
+            instance: self.instance,
+            pid: self.pid.as_raw(),
+            state,
+            started_at: self.started_on.as_secs(),
+            uptime: self.started_at.elapsed().as_secs(),
+        }
+    }
+
+    fn kill(&self) -> Result<(), nix::Error> {
+        kill(self.target(self.killasgroup), Signal::SIGKILL)
+    }
+
+    // kill_leftovers kill what remains of the process group once the child exited
+    fn kill_leftovers(&self) -> Result<bool, nix::Error> {
+        let group = self.target(true);
+
+        if !(self.stopasgroup || self.killasgroup) || kill(group, None).is_err() {
+            return Ok(false);
+        }
+        kill(group, Signal::SIGKILL)?;
+        Ok(true)
+    }
+
+    // up_at is when the child will have run for its successdelay
+    fn up_at(&self) -> time::Instant {
+        self.started_at + self.startup_time + self.paused_for()
     }
 }
 
 #[derive(Debug)]
 struct StoppingChild {
-    child: Child,
-    started_at: time::Instant,
-    startup_time: time::Duration,
+    child: RunningChild,
+    // when the current stop step started
     stopped_at: time::Instant,
-    timeout: time::Duration,
+    step: usize,
+    killed: bool,
+    // stopped for running past its timeout, it fails once gone
+    timed_out: bool,
 }
 
 impl StoppingChild {
-    fn new(
-        child: Child,
-        started_at: time::Instant,
-        startup_time: time::Duration,
-        stopped_at: time::Instant,
-        timeout: time::Duration,
-    ) -> StoppingChild {
+    fn new(child: RunningChild, stopped_at: time::Instant) -> StoppingChild {
         StoppingChild {
             child,
-            started_at,
-            startup_time,
             stopped_at,
-            timeout,
+            step: 0,
+            killed: false,
+            timed_out: false,
+        }
+    }
+
+    // deadline is the instant of the next stop step, or of the kill once they are done
+    fn deadline(&self) -> Option<time::Instant> {
+        if self.killed {
+            None
+        } else {
+            let delay = self
+                .child
+                .stop_steps
+                .get(self.step)
+                .map_or(time::Duration::ZERO, |(_, delay)| *delay);
+
+            Some(self.stopped_at + delay)
         }
     }
 
-    fn try_wait(&mut self) -> Result<Option<ExitStatus>, std::io::Error> {
-        self.child.try_wait()
+    fn has_next_step(&self) -> bool {
+        self.step + 1 < self.child.stop_steps.len()
+    }
+
+    fn next_step(&mut self, now: time::Instant) -> Result<Signal, nix::Error> {
+        self.step += 1;
+        self.stopped_at = now;
+        self.child.send_step(self.step)
     }
 
-    fn kill(&mut self) -> Result<(), std::io::Error> {
+    fn kill(&mut self) -> Result<(), nix::Error> {
+        self.killed = true;
         self.child.kill()
     }
 }
 
+// the status of an adopted child is unknown
 #[derive(Debug)]
 struct FinishedChild {
-    child: Child,
-    status: ExitStatus,
+    pid: Pid,
+    instance: u32,
+    status: Option<ExitStatus>,
     execution_time: time::Duration,
     startup_time: time::Duration,
+    timed_out: bool,
+    // stopped on purpose, like by a stop, a restart or a reload
+    stopped: bool,
 }
 
 impl FinishedChild {
-    fn new(
-        child: Child,
-        status: ExitStatus,
-        execution_time: time::Duration,
-        startup_time: time::Duration,
-    ) -> FinishedChild {
+    fn new(child: &RunningChild, status: Option<ExitStatus>) -> FinishedChild {
         FinishedChild {
-            child,
+            pid: child.pid,
+            instance: child.instance,
             status,
-            execution_time,
-            startup_time,
+            execution_time: child
+                .started_at
+                .elapsed()
+                .saturating_sub(child.paused_for()),
+            startup_time: child.startup_time,
+            timed_out: false,
+            stopped: false,
+        }
+    }
+
+    fn last_exit(&self, failure: Option<Failure>) -> LastExit {
+        LastExit {
+            instance: self.instance,
+            pid: self.pid.as_raw(),
+            exit: self.status.map_or(Exit::Unknown, Exit::from),
+            runtime: self.execution_time.as_secs(),
+            failure,
         }
     }
 }
@@ -174,6 +477,41 @@ pub struct Monitor {
 
     #[serde(skip)]
     finished: Vec<FinishedChild>,
+
+    // when to respawn a child, and the state to go back to once done
+    #[serde(skip)]
+    pending: Vec<(time::Instant, Status)>,
+
+    // the state to go back to once resumed, and when the task was paused
+    #[serde(skip)]
+    paused: Option<(Status, time::Instant)>,
+
+    // the status of the children which exited while paused, restarted once resumed
+    #[serde(skip)]
+    paused_exits: Vec<Status>,
+
+    #[serde(skip)]
+    last_exit: Option<LastExit>,
+
+    #[serde(skip)]
+    health: Option<Checker>,
+
+    // unix timestamps of the scheduled runs, none while the schedule is off
+    #[serde(skip)]
+    next_run: Option<u64>,
+
+    #[serde(skip)]
+    last_run: Option<u64>,
+
+    // a run waits for the previous one to be over
+    #[serde(skip)]
+    queued: bool,
+
+    #[serde(skip)]
+    outputs: Outputs,
+
+    #[serde(skip)]
+    tail: SharedTail,
 }
 
 impl Drop for Monitor {
@@ -186,7 +524,11 @@ impl Drop for Monitor {
 
 impl Monitor {
     // Only create Monitoring struct
-    pub fn new_only(id: String, task: Task) -> Self {
+    pub fn new(id: String, task: Task) -> Self {
+        let tail = Tail::new(id.clone(), task.numprocess > 1);
+        let health = task.healthcheck.clone().map(Checker::new);
+        let next_run = next_run(&task);
+
         Monitor {
             id,
             task,
@@ -196,23 +538,27 @@ impl Monitor {
             running: Vec::new(),
             stopping: Vec::new(),
             finished: Vec::new(),
+            pending: Vec::new(),
+            paused: None,
+            paused_exits: Vec::new(),
+            last_exit: None,
+            health,
+            next_run,
+            last_run: None,
+            queued: false,
+            outputs: Outputs::default(),
+            tail: Arc::new(Mutex::new(tail)),
         }
     }
 
-    // Create new Monitoring struct and start the task if required
-    pub fn new(id: String, task: Task) -> Self {
-        let mut mon = Monitor::new_only(id, task);
-        if mon.task.autostart {
-            mon.start();
-        }
-        mon
-    }
-
     fn change_state(&mut self, status: Status) {
         self.state = status;
     }
 
     pub fn start(&mut self) {
+        if self.next_run.is_none() {
+            self.next_run = next_run(&self.task);
+        }
         let current_status = self.status();
         if startable_state(current_status) {
             self.start_raw();
@@ -221,9 +567,40 @@ impl Monitor {
         }
     }
 
+    // wait hold the start until the dependencies are ready
+    pub fn wait(&mut self) {
+        if startable_state(self.state) {
+            log::info!("[{}] waiting for dependencies ...", self.id);
+            self.change_state(Status::Waiting);
+        } else {
+            log::warn!("[{}] already started", self.id);
+        }
+    }
+
+    pub fn is_startable(&self) -> bool {
+        startable_state(self.state)
+    }
+
+    // is_ready is true once every child has been up for its successdelay
+    pub fn is_ready(&self) -> bool {
+        self.state == Status::Active
+            && self
+                .ready_at()
+                .is_some_and(|ready_at| ready_at <= time::Instant::now())
+    }
+
+    // ready_at is when the youngest child will have been up for its successdelay
+    pub fn ready_at(&self) -> Option<time::Instant> {
+        self.running.iter().map(RunningChild::up_at).max()
+    }
+
     fn start_raw(&mut self) {
         log::info!("[{}] starting ...", self.id);
         self.retry_count = 0;
+        self.pending.clear();
+        if let Some(checker) = &mut self.health {
+            checker.reset();
+        }
         let mut running_children = self.spawn_children();
 
         self.running.append(&mut running_children);
@@ -236,178 +613,737 @@ impl Monitor {
         let mut running_children = Vec::new();
 
         for _ in 0..num_process {
-            let id = self.increase_spawned_children_counter();
-            let mut command = self.task.get_command(id, timestamp);
-
-            let running_child = spawn_child(
-                &mut command,
-                time::Duration::from_secs(self.task.successdelay.into()),
-                self.task.stopsignal,
-                time::Duration::from_secs(self.task.stopdelay.into()),
-            );
+            let running_child = self.spawn(timestamp);
             running_children.push(running_child);
         }
         running_children
     }
 
+    fn spawn(&mut self, timestamp: time::Duration) -> RunningChild {
+        let id = self.increase_spawned_children_counter();
+        let stdout = self.sink(&self.task.stdout_config(id, timestamp), id, Stream::Stdout);
+        let stderr = self.sink(&self.task.stderr_config(id, timestamp), id, Stream::Stderr);
+        let running: Vec<u32> = self.running.iter().map(|chld| chld.instance).collect();
+        let keep = self.task.numprocess as usize;
+
+        self.tail.lock().unwrap().prune(&running, keep);
+        spawn_child(
+            &mut self.task.get_command(),
+            id,
+            timestamp,
+            &self.task,
+            &mut self.outputs,
+            stdout,
+            stderr,
+        )
+    }
+
+    fn sink(&mut self, config: &OutputConfig, instance: u32, stream: Stream) -> Sink {
+        Sink {
+            output: self.outputs.open(config),
+            tail: Arc::clone(&self.tail),
+            instance,
+            stream,
+        }
+    }
+
+    // forward_handed_over forward the outputs of a child handed over, its files
+    // being appended to
+    fn forward_handed_over(
+        &mut self,
+        child: &mut RunningChild,
+        pipes: (Option<OwnedFd>, Option<OwnedFd>),
+    ) {
+        let (stdout, stderr) = pipes;
+
+        for (pipe, stream) in [(stdout, Stream::Stdout), (stderr, Stream::Stderr)] {
+            let Some(pipe) = pipe else {
+                continue;
+            };
+            let config = match stream {
+                Stream::Stdout => self.task.stdout_config(child.instance, child.started_on),
+                Stream::Stderr => self.task.stderr_config(child.instance, child.started_on),
+            };
+            let config = OutputConfig {
+                mode: OutputMode::Append,
+                ..config
+            };
+            let sink = self.sink(&config, child.instance, stream);
+
+            child
+                .pipes
+                .push((stream, self.outputs.forward(File::from(pipe), sink)));
+        }
+    }
+
+    pub fn tail(&self) -> SharedTail {
+        Arc::clone(&self.tail)
+    }
+
     pub fn status(&self) -> Status {
         self.state
     }
 
-    pub fn reload(&mut self, task: Task) {
-        if self.task != task {
-            log::info!("[{}] reloading ...", self.id);
+    pub fn detailed_status(&self) -> TaskStatus {
+        let running = self.running.iter().map(|chld| {
+            chld.status(if chld.is_paused() {
+                ChildState::Paused
+            } else {
+                ChildState::Running
+            })
+        });
+        let stopping = self
+            .stopping
+            .iter()
+            .map(|chld| chld.child.status(ChildState::Stopping));
+
+        let starting = self
+            .running
+            .iter()
+            .any(|chld| chld.up_at() > time::Instant::now());
+
+        TaskStatus {
+            status: if self.state == Status::Active && starting {
+                Status::Starting
+            } else {
+                self.state
+            },
+            retry_count: self.retry_count,
+            children: running.chain(stopping).collect(),
+            last_exit: self.last_exit.clone(),
+            health: self.health.as_ref().map(Checker::status),
+            next_run: self.next_run,
+        }
+    }
 
-            let need_to_start = task.autostart || !self.running.is_empty();
+    // reload return whether the task must be started again, which is up to the
+    // state so it waits for its dependencies
+    pub fn reload(&mut self, task: Task) -> bool {
+        if self.task == task {
+            return false;
+        }
+        log::info!("[{}] reloading ...", self.id);
 
-            self.change_state(Status::Reloading);
-            if !self.running.is_empty() {
-                self.stop();
-            }
-            self.task = task;
+        let need_to_start = task.autostart || !self.running.is_empty();
 
-            self.change_state(Status::Reloaded);
-            if need_to_start {
-                self.start();
-            } else {
-                self.change_state(Status::Inactive);
-            }
+        self.change_state(Status::Reloading);
+        if !self.running.is_empty() {
+            self.stop_children();
+        }
+        self.task = task;
+        self.next_run = next_run(&self.task);
+        self.queued = false;
+        if let Some(checker) = &mut self.health {
+            checker.cancel();
         }
+        self.health = self.task.healthcheck.clone().map(Checker::new);
+        self.tail
+            .lock()
+            .unwrap()
+            .set_prefixed(self.task.numprocess > 1);
+
+        self.change_state(Status::Reloaded);
+        if !need_to_start {
+            self.change_state(Status::Inactive);
+        }
+        need_to_start
     }
 
+    // stop the children, and the schedule until the next start
     pub fn stop(&mut self) {
+        self.next_run = None;
+        self.queued = false;
+        self.stop_children();
+    }
+
+    fn stop_children(&mut self) {
+        // a paused child would only get its stop signal once resumed
+        self.resume_children();
         log::info!("[{}] stopping ...", self.id);
         self.change_state(Status::Stopping);
+        self.pending.clear();
+        if let Some(checker) = &mut self.health {
+            checker.cancel();
+        }
         while !self.running.is_empty() {
             let chld = self.running.remove(0);
-            let stopping_child = chld.stop().unwrap();
-            self.stopping.push(stopping_child);
+            log::info!(
+                "[{}] sending {} to child-{} (step 1/{})",
+                self.id,
+                chld.stop_steps[0].0,
+                chld.pid,
+                chld.stop_steps.len()
+            );
+            // an adopted child may be gone since it was last polled
+            if let Err(e) = chld.send_step(0) {
+                log::error!("[{}] cannot stop child-{}: {}", self.id, chld.pid, e);
+                let mut finished = FinishedChild::new(&chld, None);
+
+                finished.stopped = true;
+                self.add_finished_child(finished);
+                continue;
+            }
+            self.stopping
+                .push(StoppingChild::new(chld, time::Instant::now()));
         }
         self.running.clear();
+        if !self.finished.is_empty() {
+            self.cycle_finished();
+        }
+        if self.stopping.is_empty() {
+            self.change_state(Status::Stopped);
+        }
     }
 
     pub fn kill(&mut self) {
         let mut killed_cout = 0;
         log::info!("[{}] killing ...", self.id);
+        self.pending.clear();
+        if let Some(checker) = &mut self.health {
+            checker.cancel();
+        }
 
         while !self.running.is_empty() {
-            let mut chld = self.running.remove(0);
+            let chld = self.running.remove(0);
             killed_cout += 1;
-            chld.kill().expect("cannot kill child");
+            if let Err(e) = chld.kill() {
+                log::error!("[{}] cannot kill child-{}: {}", self.id, chld.pid, e);
+            }
         }
         while !self.stopping.is_empty() {
             let mut chld = self.stopping.remove(0);
             killed_cout += 1;
-            chld.kill().expect("cannot kill chld");
+            if let Err(e) = chld.kill() {
+                log::error!("[{}] cannot kill child-{}: {}", self.id, chld.child.pid, e);
+            }
         }
         log::info!("[{}] result of killing: {} killed", self.id, killed_cout);
     }
 
     pub fn restart(&mut self) {
-        self.stop();
+        self.stop_children();
         self.start_raw();
     }
 
+    // signal send a signal to every running child, or to the one of an instance
+    pub fn signal(&self, signal: Signal, instance: Option<u32>) -> Result<(), String> {
+        let children: Vec<&RunningChild> = self
+            .running
+            .iter()
+            .filter(|chld| instance.is_none_or(|instance| chld.instance == instance))
+            .collect();
+
+        if children.is_empty() {
+            return Err(match instance {
+                Some(instance) => format!("{} has no running instance {}", self.id, instance),
+                None => format!("{} is not running", self.id),
+            });
+        }
+        for chld in children {
+            log::info!("[{}] sending {} to child-{}", self.id, signal, chld.pid);
+            kill(chld.pid, signal)
+                .map_err(|e| format!("cannot send {} to child-{}: {}", signal, chld.pid, e))?;
+        }
+        Ok(())
+    }
+
+    // pause freeze the running children, keeping them in memory
+    pub fn pause(&mut self) -> Result<(), String> {
+        if self.paused.is_some() {
+            return Err(format!("{} is already paused", self.id));
+        }
+        if self.running.is_empty()
+            || !matches!(
+                self.state,
+                Status::Active | Status::Failing | Status::Unhealthy | Status::Backoff
+            )
+        {
+            return Err(format!("{} is not running", self.id));
+        }
+        log::info!("[{}] pausing ...", self.id);
+        for chld in &mut self.running {
+            if let Err(e) = chld.pause() {
+                log::error!("[{}] cannot pause child-{}: {}", self.id, chld.pid, e);
+            }
+        }
+        self.paused = Some((self.state, time::Instant::now()));
+        self.change_state(Status::Paused);
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<(), String> {
+        let Some((state, _)) = self.paused else {
+            return Err(format!("{} is not paused", self.id));
+        };
+        let exits = mem::take(&mut self.paused_exits);
+
+        log::info!("[{}] resuming ...", self.id);
+        self.resume_children();
+        self.change_state(state);
+        for status in exits {
+            if status == Status::Failed && !is_settled(self.state) {
+                self.failing();
+            }
+            self.restart_task(status, 1);
+        }
+        Ok(())
+    }
+
+    // resume_children also push the pending respawns back by the time spent paused,
+    // and drop the restarts due meanwhile, resume takes them first
+    fn resume_children(&mut self) {
+        let Some((_, paused_at)) = self.paused.take() else {
+            return;
+        };
+        let paused_for = paused_at.elapsed();
+
+        self.paused_exits.clear();
+
+        for (at, _) in &mut self.pending {
+            *at += paused_for;
+        }
+        for chld in self.running.iter_mut().filter(|chld| chld.is_paused()) {
+            if let Err(e) = chld.resume() {
+                log::error!("[{}] cannot resume child-{}: {}", self.id, chld.pid, e);
+            }
+        }
+    }
+
     pub fn get_task(&self) -> &Task {
         &self.task
     }
 
-    pub fn is_running(&self) -> bool {
-        !self.running.is_empty() || !self.stopping.is_empty()
+    // saved is what a new server needs to adopt the running children
+    pub fn saved(&self) -> SavedMonitor {
+        SavedMonitor {
+            spawned_children: self.spawned_children,
+            retry_count: self.retry_count,
+            children: self.running.iter().map(RunningChild::saved).collect(),
+            last_run: self.last_run,
+        }
     }
 
-    pub fn has_finished(&self) -> bool {
-        self.running.is_empty() && self.stopping.is_empty()
+    // adopt take over the children a previous server left running, return
+    // false when none of them is still alive
+    pub fn adopt(&mut self, saved: &SavedMonitor, handed_over: bool) -> bool {
+        self.spawned_children = self.spawned_children.max(saved.spawned_children);
+        self.last_run = saved.last_run;
+        self.missed_runs();
+        for child in &saved.children {
+            // the pipes of a child gone are closed
+            let pipes = handed_over.then(|| child.pipes());
+
+            if child.is_alive() {
+                log::info!("[{}] adopting child-{}", self.id, child.pid);
+                let mut adopted = RunningChild::adopt(child, &self.task, handed_over);
+
+                if let Some(pipes) = pipes {
+                    self.forward_handed_over(&mut adopted, pipes);
+                }
+                self.running.push(adopted);
+            } else {
+                log::warn!("[{}] child-{} is gone, not adopted", self.id, child.pid);
+            }
+        }
+        if self.running.is_empty() {
+            return false;
+        }
+        self.retry_count = saved.retry_count;
+        self.change_state(Status::Active);
+        true
     }
 
-    pub fn cycle(&mut self, sender: &Sender<Inter>) {
-        log::debug!(
-            "[{}] doing cycle: {} running, {} stopping ",
+    // release forget the children, so they keep running once the server left,
+    // and return what the next server needs to adopt them with their outputs
+    pub fn release(&mut self) -> SavedMonitor {
+        self.resume_children();
+        log::info!(
+            "[{}] leaving {} children running",
             self.id,
-            self.running.len(),
-            self.stopping.len()
+            self.running.len()
         );
+        self.pending.clear();
+        if let Some(checker) = &mut self.health {
+            checker.cancel();
+        }
+        self.outputs.stop_forwarding();
+
+        let id = &self.id;
+        let saved = SavedMonitor {
+            children: self
+                .running
+                .iter_mut()
+                .map(|chld| chld.hand_over(id))
+                .collect(),
+            ..self.saved()
+        };
+        self.running.clear();
+        self.stopping.clear();
+        saved
+    }
 
-        if !self.running.is_empty() {
-            self.cycle_running().unwrap();
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty() || !self.stopping.is_empty()
+    }
+
+    pub fn next_deadline(&self) -> Option<time::Instant> {
+        let stopping = self.stopping.iter().filter_map(StoppingChild::deadline);
+        let pending = self
+            .pending
+            .iter()
+            .filter(|_| self.paused.is_none())
+            .map(|(at, _)| *at);
+        let recovering = self
+            .running
+            .iter()
+            .filter(|chld| self.recovering() && !chld.is_paused())
+            .map(RunningChild::up_at)
+            .filter(|up_at| *up_at > time::Instant::now());
+        let timeouts = self
+            .running
+            .iter()
+            .filter(|chld| !chld.is_paused())
+            .filter_map(RunningChild::timeout_at);
+
+        let health = self
+            .health
+            .as_ref()
+            .filter(|_| self.checking())
+            .map(Checker::deadline);
+        let adopted = self
+            .running
+            .iter()
+            .chain(self.stopping.iter().map(|chld| &chld.child))
+            .any(|chld| chld.adopted)
+            .then(|| time::Instant::now() + ADOPTED_POLL);
+        let scheduled = self.next_run.map(|at| {
+            time::Instant::now()
+                + time::Duration::from_secs(at).saturating_sub(get_current_timestamp())
+        });
+
+        stopping
+            .chain(pending)
+            .chain(recovering)
+            .chain(timeouts)
+            .chain(health)
+            .chain(adopted)
+            .chain(scheduled)
+            .min()
+    }
+
+    // checking is true while the children are expected to be healthy
+    fn checking(&self) -> bool {
+        !self.running.is_empty()
+            && matches!(
+                self.state,
+                Status::Active | Status::Failing | Status::Unhealthy
+            )
+    }
+
+    // recovering is true while a successful start would reset the retries, a
+    // health checked task must also have passed a check since it was restarted
+    fn recovering(&self) -> bool {
+        self.task.successdelay > 0
+            && (self.retry_count > 0 || self.state == Status::Failing)
+            && self.health.as_ref().is_none_or(Checker::has_passed)
+    }
+
+    // cycle kill the stopping children that didn't exit before their stop delay,
+    // respawn the children waiting for their backoff and reset the retries of
+    // a task whose children stayed up long enough
+    pub fn cycle(&mut self, event: &Sender<Inter>) {
+        let now = time::Instant::now();
+
+        self.cycle_stopping(now);
+        self.cycle_timeout(now);
+        self.cycle_adopted();
+        self.cycle_pending(now);
+        self.cycle_health(now, event);
+        self.cycle_schedule();
+        if self.recovering() && self.running.iter().any(|chld| chld.up_at() <= now) {
+            log::info!("[{}] recovered, reset retries", self.id);
+            self.retry_count = 0;
+            if self.state == Status::Failing {
+                self.change_state(Status::Active);
+            }
+        }
+    }
+
+    // missed_runs run the task once if a run was due while the server was down
+    fn missed_runs(&mut self) {
+        let (Some(schedule), Some(last_run)) = (&self.task.schedule, self.last_run) else {
+            return;
+        };
+        let now = get_current_timestamp().as_secs();
+
+        if schedule.next_after(last_run).is_none_or(|at| at > now) {
+            return;
         }
-        if !self.stopping.is_empty() {
-            self.cycle_stopping().unwrap();
+        match schedule.missed {
+            Missed::Skip => log::warn!("[{}] skipping the runs missed while down", self.id),
+            Missed::RunOnce => {
+                log::info!("[{}] running once for the runs missed while down", self.id);
+                self.next_run = Some(now);
+            }
         }
-        if !self.finished.is_empty() {
-            self.cycle_finished(sender);
+    }
+
+    // cycle_schedule start the runs that are due, unless the previous one is
+    // still running, and the queued run once the previous one is over
+    fn cycle_schedule(&mut self) {
+        let Some(schedule) = &self.task.schedule else {
+            return;
+        };
+        // the runs due while paused wait for the resume
+        if self.paused.is_some() {
+            return;
+        }
+        let overlap = schedule.overlap;
+        let now = get_current_timestamp().as_secs();
+        let busy = self.is_running() || !self.pending.is_empty();
+
+        if self.queued && !busy {
+            log::info!("[{}] starting the queued run", self.id);
+            self.queued = false;
+            self.run_scheduled(now);
+            return;
+        }
+        if self.next_run.is_none_or(|at| at > now) {
+            return;
+        }
+        self.next_run = schedule.next_after(now);
+        if !busy {
+            log::info!("[{}] starting the scheduled run", self.id);
+            self.run_scheduled(now);
+            return;
+        }
+        match overlap {
+            Overlap::Skip => log::warn!("[{}] still running, skipping this run", self.id),
+            Overlap::Queue => {
+                log::info!("[{}] still running, queuing this run", self.id);
+                self.queued = true;
+            }
+            Overlap::Kill => {
+                log::warn!("[{}] still running, stopping the previous run", self.id);
+                self.stop_children();
+                self.queued = true;
+            }
         }
     }
 
-    // cycle_running check for Child that has terminated
-    fn cycle_running(&mut self) -> Result<(), std::io::Error> {
-        let mut i = 0;
+    fn run_scheduled(&mut self, now: u64) {
+        self.last_run = Some(now);
+        self.start_raw();
+    }
 
-        while i != self.running.len() {
-            if let Some(st) = self.running[i].try_wait()? {
-                let e = self.running.remove(i);
-                self.add_finished_child(e.child, st, e.started_at.elapsed(), e.startup_time);
-            } else {
-                i += 1;
+    // cycle_timeout stop the children running past their timeout
+    fn cycle_timeout(&mut self, now: time::Instant) {
+        let (expired, running): (Vec<_>, Vec<_>) = self
+            .running
+            .drain(..)
+            .partition(|chld| chld.timeout_at().is_some_and(|at| at <= now));
+
+        self.running = running;
+        for chld in expired {
+            log::warn!(
+                "[{}] child-{} timed out after {}s, stopping it",
+                self.id,
+                chld.pid,
+                chld.started_at.elapsed().as_secs()
+            );
+            // a child already gone fails once reaped
+            if let Err(e) = chld.send_step(0) {
+                log::error!("[{}] cannot stop child-{}: {}", self.id, chld.pid, e);
             }
+            let mut stopping = StoppingChild::new(chld, now);
+
+            stopping.timed_out = true;
+            self.stopping.push(stopping);
         }
+    }
 
-        Ok(())
+    // cycle_adopted find the adopted children gone since the last cycle
+    fn cycle_adopted(&mut self) {
+        let gone: Vec<Pid> = self
+            .running
+            .iter()
+            .chain(self.stopping.iter().map(|chld| &chld.child))
+            .filter(|chld| chld.adopted && !persist::is_alive(chld.pid))
+            .map(|chld| chld.pid)
+            .collect();
+
+        for pid in gone {
+            self.child_finished(pid, None);
+        }
     }
 
-    // cycle_stopping check for signaled children if they've stop
-    fn cycle_stopping(&mut self) -> Result<(), std::io::Error> {
-        let mut killed: Vec<StoppingChild> = Vec::new();
-        let mut i = 0;
+    fn cycle_pending(&mut self, now: time::Instant) {
+        // the respawns wait for the resume
+        if self.paused.is_some() {
+            return;
+        }
+        let (due, pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(at, _)| *at <= now);
 
-        while i != self.stopping.len() {
-            let chld = &mut self.stopping[i];
-            let timeout = chld.timeout;
-            let during = chld.stopped_at.elapsed();
+        self.pending = pending;
+        for (_, state) in due {
+            log::info!("[{}] retry process", self.id);
+            let running_child = self.spawn(get_current_timestamp());
+            self.running.push(running_child);
+            if self.pending.is_empty() && self.state == Status::Backoff {
+                self.change_state(state);
+            }
+        }
+    }
+
+    fn cycle_health(&mut self, now: time::Instant, event: &Sender<Inter>) {
+        if !self.checking() {
+            return;
+        }
+        if let Some(result) = self
+            .health
+            .as_mut()
+            .and_then(|checker| checker.poll(now, event))
+        {
+            self.health_checked(result);
+        }
+    }
+
+    // health_checked mark the task unhealthy once too many checks failed in a row,
+    // and restart it if asked to
+    fn health_checked(&mut self, result: Result<(), String>) {
+        let (unhealthy, restart) = match &self.health {
+            Some(checker) => (checker.is_unhealthy(), checker.config().restart),
+            None => return,
+        };
 
-            if let Some(st) = chld.try_wait()? {
-                let e = self.stopping.remove(i);
-                self.add_finished_child(e.child, st, e.started_at.elapsed(), e.startup_time);
-            } else if during > timeout {
-                chld.kill()?;
-                let e = self.stopping.remove(i);
-                killed.push(e);
+        match result {
+            Ok(()) if self.state == Status::Unhealthy => {
+                log::info!("[{}] healthy again", self.id);
+                self.change_state(Status::Active);
+            }
+            Ok(()) => {}
+            Err(e) => {
+                log::warn!("[{}] health check failed: {}", self.id, e);
+                if unhealthy && self.state != Status::Unhealthy {
+                    log::warn!("[{}] unhealthy", self.id);
+                    self.change_state(Status::Unhealthy);
+                    if restart {
+                        self.restart_unhealthy();
+                    }
+                }
+            }
+        }
+    }
+
+    // restart_unhealthy stop the children and respawn them after the backoff delay,
+    // the restart counting as a retry until a health check passes again
+    fn restart_unhealthy(&mut self) {
+        self.stop_children();
+        if let Some(checker) = &mut self.health {
+            checker.reset();
+        }
+        self.change_state(Status::Failing);
+        self.restart_task(Status::Failed, self.task.numprocess);
+    }
+
+    fn cycle_stopping(&mut self, now: time::Instant) {
+        for chld in &mut self.stopping {
+            if chld.deadline().is_none_or(|deadline| deadline > now) {
+                continue;
+            }
+            if chld.has_next_step() {
+                match chld.next_step(now) {
+                    Ok(signal) => log::warn!(
+                        "[{}] child-{} didn't stop yet, sending {} (step {}/{})",
+                        self.id,
+                        chld.child.pid,
+                        signal,
+                        chld.step + 1,
+                        chld.child.stop_steps.len()
+                    ),
+                    Err(e) => {
+                        log::error!(
+                            "[{}] cannot signal child-{}: {}",
+                            self.id,
+                            chld.child.pid,
+                            e
+                        );
+                    }
+                }
             } else {
-                i += 1;
+                log::warn!(
+                    "[{}] child-{} didn't stop in time, killing it",
+                    self.id,
+                    chld.child.pid
+                );
+                if let Err(e) = chld.kill() {
+                    log::error!("[{}] cannot kill child-{}: {}", self.id, chld.child.pid, e);
+                }
             }
         }
+    }
+
+    // child_exited handle a reaped child, return false if it isn't one of ours
+    pub fn child_exited(&mut self, pid: Pid, status: ExitStatus) -> bool {
+        let checked = self
+            .health
+            .as_mut()
+            .and_then(|checker| checker.exited(pid, status));
 
-        while !killed.is_empty() {
-            let mut chld = killed.remove(0);
-            let st = chld.child.wait()?;
-            self.add_finished_child(chld.child, st, chld.started_at.elapsed(), chld.startup_time);
+        if let Some(result) = checked {
+            self.health_checked(result);
+            true
+        } else {
+            self.child_finished(pid, Some(status))
         }
-        Ok(())
     }
 
-    fn add_finished_child(
-        &mut self,
-        child: Child,
-        status: ExitStatus,
-        execution_time: time::Duration,
-        startup_time: time::Duration,
-    ) {
+    // child_finished handle a child gone, with its status unless it was adopted
+    fn child_finished(&mut self, pid: Pid, status: Option<ExitStatus>) -> bool {
+        if let Some(i) = self.running.iter().position(|chld| chld.pid == pid) {
+            let e = self.running.remove(i);
+            self.add_finished_child(FinishedChild::new(&e, status));
+        } else if let Some(i) = self.stopping.iter().position(|chld| chld.child.pid == pid) {
+            let e = self.stopping.remove(i);
+            match e.child.kill_leftovers() {
+                Ok(true) => log::warn!(
+                    "[{}] descendants of child-{} survived, killed them",
+                    self.id,
+                    pid
+                ),
+                Ok(false) => {}
+                Err(err) => log::error!(
+                    "[{}] cannot kill descendants of child-{}: {}",
+                    self.id,
+                    pid,
+                    err
+                ),
+            }
+            let mut finished = FinishedChild::new(&e.child, status);
+
+            finished.timed_out = e.timed_out;
+            finished.stopped = !e.timed_out;
+            self.add_finished_child(finished);
+        } else {
+            return false;
+        }
+        self.cycle_finished();
+        true
+    }
+
+    fn add_finished_child(&mut self, child: FinishedChild) {
         log::debug!(
             "[{}] child-{} exited with {} after {}s",
             self.id,
-            child.id(),
-            status,
-            execution_time.as_secs()
+            child.pid,
+            child.status.map_or(Exit::Unknown, Exit::from),
+            child.execution_time.as_secs()
         );
-        self.finished.push(FinishedChild::new(
-            child,
-            status,
-            execution_time,
-            startup_time,
-        ))
+        self.finished.push(child);
     }
 
-    fn cycle_finished(&mut self, _sender: &Sender<Inter>) {
+    fn cycle_finished(&mut self) {
         log::debug!(
             "[{}] end of cycle: {} finished",
             self.id,
@@ -415,37 +1351,83 @@ impl Monitor {
         );
         while !self.finished.is_empty() {
             let e = self.finished.remove(0);
-            let status = self.check_finished_child(&e);
-            match status {
-                Status::Failed => {
-                    if self.state != Status::Stopping {
-                        self.change_state(Status::Failing)
-                    }
-                }
-                Status::Finished => {}
-                _ => panic!("unexpected status for finished child !"),
+            // the task may be started again already, it is none of its business
+            if e.stopped {
+                self.last_exit = Some(e.last_exit(None));
+                continue;
             }
-            if self.should_process_restarted(status) {
-                self.restart_task()
+            let failure = self.check_finished_child(&e);
+            let status = if failure.is_some() {
+                Status::Failed
+            } else {
+                Status::Finished
+            };
+            if status == Status::Failed && !is_settled(self.state) {
+                self.failing();
+            }
+            // a child up for its successdelay means the task recovered
+            if !e.startup_time.is_zero()
+                && e.execution_time >= e.startup_time
+                && self.health.as_ref().is_none_or(Checker::has_passed)
+            {
+                self.retry_count = 0;
+            }
+            self.last_exit = Some(e.last_exit(failure));
+            if self.state == Status::Stopping || !self.should_process_restarted(status) {
+                continue;
+            }
+            if self.paused.is_some() {
+                self.paused_exits.push(status);
+            } else {
+                self.restart_task(status, 1);
             }
         }
-        if self.running.is_empty() && self.stopping.is_empty() {
+        // a waiting task has no child yet, the last ones were stopped by a reload
+        if self.running.is_empty()
+            && self.stopping.is_empty()
+            && self.pending.is_empty()
+            && self.paused_exits.is_empty()
+            && self.state != Status::Waiting
+        {
+            // nothing is left to resume
+            if let Some((state, _)) = self.paused.take() {
+                self.change_state(state);
+            }
             log::info!("[{}] finished", self.id);
             self.change_state(finished_state(self.state));
         }
     }
 
+    // failing keep the backoff status while respawns are pending, the task
+    // being failing once they are done
+    fn failing(&mut self) {
+        if self.state == Status::Backoff && !self.pending.is_empty() {
+            for (_, state) in &mut self.pending {
+                *state = Status::Failing;
+            }
+        } else {
+            self.change_state(Status::Failing);
+        }
+    }
+
     fn should_process_restarted(&self, status: Status) -> bool {
-        (self.retry_count < self.task.retry)
-            && ((status == Status::Failed && self.task.restart == Relaunch::OnError)
-                || (status == Status::Finished && self.task.restart == Relaunch::Always))
+        (status == Status::Failed && self.task.restart == Relaunch::OnError)
+            || (status == Status::Finished && self.task.restart == Relaunch::Always)
     }
 
-    fn check_finished_child(&self, child: &FinishedChild) -> Status {
-        child.status.code().map_or_else(
+    fn check_finished_child(&self, child: &FinishedChild) -> Option<Failure> {
+        if child.timed_out {
+            return Some(Failure::TimedOut);
+        }
+        let Some(status) = child.status else {
+            log::warn!("[{}] adopted child-{} vanished", self.id, child.pid);
+            return Some(Failure::Vanished);
+        };
+
+        status.code().map_or_else(
             || {
-                log::warn!("[{}] unexpected exit status {}", self.id, child.status);
-                Status::Failed
+                log::warn!("[{}] unexpected exit status {}", self.id, status);
+                Some(Failure::Signaled)
             },
             |code| {
                 if self.unexpected_exit_code(code) {
@@ -454,39 +1436,50 @@ impl Monitor {
                         self.id,
                         code
                     );
-                    Status::Failed
+                    Some(Failure::UnexpectedExitCode)
                 } else if child.execution_time < child.startup_time {
                     log::warn!("[{}] child finished too early", self.id);
-                    Status::Failed
+                    Some(Failure::ExitedTooEarly)
                 } else {
-                    Status::Finished
+                    None
                 }
             },
         )
     }
 
     fn unexpected_exit_code(&self, code: i32) -> bool {
-        !self.task.exitcodes.iter().any(|&wanted| wanted == code)
+        !self.task.exitcodes.contains(&code)
     }
 
-    fn restart_task(&mut self) {
-        let timestamp = get_current_timestamp();
+    // restart_task respawn children after their backoff delay, or give up
+    fn restart_task(&mut self, status: Status, children: u32) {
+        if self.retry_count >= self.task.retry {
+            log::warn!("[{}] max retries limit", self.id);
+            self.change_state(Status::Fatal);
+            return;
+        }
+        let delay = self.task.backoff.delay(self.retry_count);
+        let state = if status == Status::Failed {
+            Status::Failing
+        } else {
+            Status::Active
+        };
 
-        if self.retry_count < self.task.retry {
-            let id = self.increase_spawned_children_counter();
-            let mut command = self.task.get_command(id, timestamp);
-            self.retry_count += 1;
+        self.retry_count += 1;
+        if delay.is_zero() {
+            let timestamp = get_current_timestamp();
 
-            let running_child = spawn_child(
-                &mut command,
-                time::Duration::from_secs(self.task.successdelay.into()),
-                self.task.stopsignal,
-                time::Duration::from_secs(self.task.stopdelay.into()),
-            );
-            log::info!("[{}] retry process", self.id);
-            self.running.push(running_child);
+            for _ in 0..children {
+                let running_child = self.spawn(timestamp);
+                log::info!("[{}] retry process", self.id);
+                self.running.push(running_child);
+            }
         } else {
-            log::warn!("[{}] max retries limit", self.id);
+            log::info!("[{}] retry process in {:.1}s", self.id, delay.as_secs_f64());
+            let at = time::Instant::now() + delay;
+
+            self.pending.extend((0..children).map(|_| (at, state)));
+            self.change_state(Status::Backoff);
         }
     }
 
@@ -501,61 +1494,411 @@ impl Monitor {
 impl Debug for Monitor {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         if let Ok(s) = serde_json::to_string(self) {
-            write!(f, "{}", s)
+            write!(f, "{s}")
         } else {
             Err(fmt::Error)
         }
     }
 }
 
+// next_run is the first run of a scheduled task from now
+fn next_run(task: &Task) -> Option<u64> {
+    task.schedule
+        .as_ref()
+        .and_then(|schedule| schedule.next_after(get_current_timestamp().as_secs()))
+}
+
 fn startable_state(state: Status) -> bool {
     state == Status::Inactive
         || state == Status::Finished
         || state == Status::Failed
         || state == Status::Stopped
         || state == Status::Reloaded
+        || state == Status::Fatal
+        || state == Status::Waiting
+}
+
+// settled states are not changed by a child failing
+fn is_settled(state: Status) -> bool {
+    state == Status::Stopping || state == Status::Fatal || state == Status::Paused
 }
 
 fn finished_state(state: Status) -> Status {
     match state {
         Status::Stopping | Status::Stopped => Status::Stopped,
-        Status::Failing | Status::Failed => Status::Failed,
+        Status::Failing | Status::Failed | Status::Unhealthy => Status::Failed,
+        Status::Fatal => Status::Fatal,
         _ => Status::Finished,
     }
 }
 
+// children are reaped by the server on SIGCHLD, see reaper::reap
+#[allow(clippy::cast_possible_wrap, clippy::zombie_processes)]
 fn spawn_child(
     command: &mut Command,
-    startup_time: time::Duration,
-    stopsignal: Signal,
-    stopdelay: time::Duration,
+    instance: u32,
+    started_on: time::Duration,
+    task: &Task,
+    outputs: &mut Outputs,
+    stdout: Sink,
+    stderr: Sink,
 ) -> RunningChild {
-    let child = command.spawn().expect("Cannot start child");
-    RunningChild::new(
-        child,
-        time::Instant::now(),
-        startup_time,
-        stopsignal,
-        stopdelay,
-    )
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Cannot start child");
+    let mut running =
+        RunningChild::new(Pid::from_raw(child.id() as i32), instance, started_on, task);
+
+    if let Some(pipe) = child.stdout.take() {
+        running
+            .pipes
+            .push((Stream::Stdout, outputs.forward(pipe, stdout)));
+    }
+    if let Some(pipe) = child.stderr.take() {
+        running
+            .pipes
+            .push((Stream::Stderr, outputs.forward(pipe, stderr)));
+    }
+    running
 }
 
 #[cfg(test)]
 mod monitor_suite {
-    use super::{finished_state, startable_state, Status};
+    use super::{
+        finished_state, startable_state, Exit, Failure, FinishedChild, LastExit, Monitor,
+        RunningChild, Status,
+    };
+    use crate::server::{persist, task::Task};
+    use nix::{sys::wait::waitpid, unistd::Pid};
+    use std::{
+        env, fs,
+        os::unix::process::{CommandExt, ExitStatusExt},
+        process::{self, Command, ExitStatus},
+        sync::mpsc::channel,
+        thread, time,
+    };
+
+    #[test]
+    fn test_exit_from_status() {
+        assert_eq!(Exit::from(ExitStatus::from_raw(3 << 8)), Exit::Code(3));
+        assert_eq!(
+            Exit::from(ExitStatus::from_raw(9)),
+            Exit::Signal(String::from("SIGKILL"))
+        );
+    }
 
     #[test]
+    fn test_last_exit_display() {
+        let mut last_exit = LastExit {
+            instance: 2,
+            pid: 1234,
+            exit: Exit::Code(1),
+            runtime: 0,
+            failure: Some(Failure::ExitedTooEarly),
+        };
+        assert_eq!(
+            last_exit.to_string(),
+            "instance 2: pid 1234, exit code 1 after 0s, failed: exited before successdelay"
+        );
+
+        last_exit.exit = Exit::Signal(String::from("SIGTERM"));
+        last_exit.failure = None;
+        assert_eq!(
+            last_exit.to_string(),
+            "instance 2: pid 1234, killed by SIGTERM after 0s"
+        );
+    }
+
+    #[test]
+    fn test_timeout_at() {
+        let task = |raw: &str| serde_yaml::from_str::<Task>(raw).unwrap();
+        let started_on = time::Duration::from_secs(1_600_000_000);
+
+        let child = RunningChild::new(Pid::from_raw(1), 0, started_on, &task("{cmd: ls}"));
+        assert_eq!(child.timeout_at(), None);
+
+        let child = RunningChild::new(
+            Pid::from_raw(1),
+            0,
+            started_on,
+            &task("{cmd: ls, timeout: 5}"),
+        );
+        assert_eq!(
+            child.timeout_at(),
+            Some(child.started_at + time::Duration::from_secs(5))
+        );
+        assert_eq!(Failure::TimedOut.to_string(), "timed out");
+    }
+
+    #[test]
+    fn test_paused_time() {
+        let task = serde_yaml::from_str::<Task>("{cmd: ls, timeout: 5, successdelay: 2}").unwrap();
+        let mut child = RunningChild::new(Pid::from_raw(1), 0, time::Duration::ZERO, &task);
+        let paused = time::Duration::from_secs(3);
+
+        child.paused = paused;
+        assert!(!child.is_paused());
+        assert_eq!(
+            child.up_at(),
+            child.started_at + time::Duration::from_secs(2) + paused
+        );
+        assert_eq!(
+            child.timeout_at(),
+            Some(child.started_at + time::Duration::from_secs(5) + paused)
+        );
+
+        child.paused_at = Some(time::Instant::now());
+        assert!(child.is_paused());
+        assert!(child.paused_for() >= paused);
+    }
+
+    #[test]
+    fn test_failure_during_backoff() {
+        let task = serde_yaml::from_str::<Task>("{cmd: ls, numprocess: 2}").unwrap();
+        let mut monitor = Monitor::new(String::from("web"), task.clone());
+        let child = RunningChild::new(Pid::from_raw(1), 1, time::Duration::ZERO, &task);
+        let respawn_at = time::Instant::now() + time::Duration::from_secs(30);
+
+        monitor.state = Status::Backoff;
+        monitor.pending.push((respawn_at, Status::Active));
+        monitor.finished.push(FinishedChild::new(
+            &child,
+            Some(ExitStatus::from_raw(1 << 8)),
+        ));
+        monitor.cycle_finished();
+        assert_eq!(monitor.state, Status::Backoff);
+        assert_eq!(monitor.pending, vec![(respawn_at, Status::Failing)]);
+
+        monitor.pending.clear();
+        monitor.state = Status::Active;
+        monitor.running.push(child);
+        monitor.finished.push(FinishedChild::new(
+            &monitor.running[0],
+            Some(ExitStatus::from_raw(1 << 8)),
+        ));
+        monitor.cycle_finished();
+        assert_eq!(monitor.state, Status::Failing);
+        monitor.running.clear();
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn test_stop_vanished_child() {
+        let task = serde_yaml::from_str::<Task>("{cmd: ls}").unwrap();
+        let mut monitor = Monitor::new(String::from("web"), task.clone());
+        let mut gone = Command::new("true").spawn().unwrap();
+        let pid = Pid::from_raw(gone.id() as i32);
+        let mut child = RunningChild::new(pid, 0, time::Duration::ZERO, &task);
+
+        gone.wait().unwrap();
+        child.adopted = true;
+        monitor.state = Status::Active;
+        monitor.running.push(child);
+        monitor.stop_children();
+        assert_eq!(monitor.state, Status::Stopped);
+        assert!(monitor.running.is_empty() && monitor.stopping.is_empty());
+        assert_eq!(monitor.last_exit.as_ref().unwrap().pid, pid.as_raw());
+    }
+
+    #[test]
+    fn test_stopped_child() {
+        let task = serde_yaml::from_str::<Task>("{cmd: ls, restart: always}").unwrap();
+        let mut monitor = Monitor::new(String::from("web"), task.clone());
+        let child = RunningChild::new(Pid::from_raw(1), 0, time::Duration::ZERO, &task);
+        let mut finished = FinishedChild::new(&child, Some(ExitStatus::from_raw(15)));
+
+        // a reload already started the task again
+        monitor.state = Status::Waiting;
+        finished.stopped = true;
+        monitor.finished.push(finished);
+        monitor.cycle_finished();
+        assert_eq!(monitor.state, Status::Waiting);
+        assert!(monitor.pending.is_empty() && monitor.running.is_empty());
+        assert_eq!(monitor.last_exit.as_ref().unwrap().failure, None);
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn test_pause_during_backoff() {
+        let task = serde_yaml::from_str::<Task>("{cmd: ls, numprocess: 2}").unwrap();
+        let mut monitor = Monitor::new(String::from("web"), task.clone());
+        let mut sleep = Command::new("sleep")
+            .arg("10")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = Pid::from_raw(sleep.id() as i32);
+        let now = time::Instant::now();
+        let respawn_at = now + time::Duration::from_secs(1);
+
+        monitor.state = Status::Backoff;
+        monitor
+            .running
+            .push(RunningChild::new(pid, 0, time::Duration::ZERO, &task));
+        monitor.pending.push((respawn_at, Status::Active));
+        monitor.pause().unwrap();
+        assert_eq!(monitor.state, Status::Paused);
+
+        // no child is spawned while paused, and the respawn isn't a deadline
+        monitor.cycle_pending(now + time::Duration::from_secs(2));
+        assert_eq!(monitor.running.len(), 1);
+        assert_eq!(monitor.pending.len(), 1);
+        assert!(monitor.next_deadline().is_none_or(|at| at != respawn_at));
+
+        monitor.paused = Some((
+            Status::Backoff,
+            now.checked_sub(time::Duration::from_secs(5)).unwrap(),
+        ));
+        monitor.resume().unwrap();
+        assert_eq!(monitor.state, Status::Backoff);
+        assert!(monitor.pending[0].0 >= respawn_at + time::Duration::from_secs(5));
+
+        monitor.running.clear();
+        sleep.kill().unwrap();
+        sleep.wait().unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn test_exit_during_pause() {
+        let task =
+            serde_yaml::from_str::<Task>("{cmd: sleep 10, restart: on-error, retry: 1}").unwrap();
+        let mut monitor = Monitor::new(String::from("web"), task.clone());
+        let mut sleep = Command::new("sleep")
+            .arg("10")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = Pid::from_raw(sleep.id() as i32);
+
+        monitor.state = Status::Active;
+        monitor
+            .running
+            .push(RunningChild::new(pid, 0, time::Duration::ZERO, &task));
+        monitor.pause().unwrap();
+        sleep.kill().unwrap();
+        sleep.wait().unwrap();
+
+        // the restart waits for the resume, the task staying paused meanwhile
+        let child = monitor.running.remove(0);
+        monitor.finished.push(FinishedChild::new(
+            &child,
+            Some(ExitStatus::from_raw(1 << 8)),
+        ));
+        monitor.cycle_finished();
+        assert_eq!(monitor.state, Status::Paused);
+        assert!(monitor.running.is_empty() && monitor.pending.is_empty());
+
+        monitor.resume().unwrap();
+        assert_eq!((monitor.state, monitor.retry_count), (Status::Failing, 1));
+        assert_eq!(monitor.running.len(), 1);
+        monitor.stop_children();
+        for chld in monitor.stopping.drain(..) {
+            waitpid(chld.child.pid, None).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unhealthy_restart() {
+        let task = serde_yaml::from_str::<Task>(
+            "{cmd: sleep 10, numprocess: 2, retry: 1, backoff_initial: 1, successdelay: 1, healthcheck: {tcp: '127.0.0.1:1', retries: 1, restart: true}}",
+        )
+        .unwrap();
+        let mut monitor = Monitor::new(String::from("web"), task);
+        let (event, checked) = channel();
+        let fail_check = |monitor: &mut Monitor| {
+            let later = time::Instant::now() + time::Duration::from_secs(30);
+
+            monitor.cycle_health(later, &event);
+            checked.recv_timeout(time::Duration::from_secs(5)).unwrap();
+            monitor.cycle_health(later, &event);
+        };
+
+        monitor.start_raw();
+        fail_check(&mut monitor);
+        assert_eq!(monitor.state, Status::Backoff);
+        assert_eq!(monitor.retry_count, 1);
+        assert_eq!((monitor.running.len(), monitor.pending.len()), (0, 2));
+
+        // the respawned children up for their successdelay don't reset the retries
+        monitor.cycle_pending(time::Instant::now() + time::Duration::from_secs(30));
+        assert_eq!((monitor.state, monitor.running.len()), (Status::Failing, 2));
+        assert!(!monitor.recovering());
+
+        fail_check(&mut monitor);
+        assert_eq!(monitor.state, Status::Fatal);
+        assert!(monitor.running.is_empty() && monitor.pending.is_empty());
+        for chld in monitor.stopping.drain(..) {
+            waitpid(chld.child.pid, None).unwrap();
+        }
+    }
+
+    // counted is the number of lines written, checking none is missing
+    fn counted(path: &std::path::Path) -> usize {
+        let out = fs::read_to_string(path).unwrap();
+
+        for (i, line) in out.lines().enumerate() {
+            assert_eq!(line, i.to_string());
+        }
+        out.lines().count()
+    }
+
+    #[test]
+    fn test_handover() {
+        let dir = env::temp_dir().join(format!("taskmaster-handover-{}", process::id()));
+        let out = dir.join("out.log");
+        let task = serde_yaml::from_str::<Task>(&format!(
+            "cmd: sh -c 'i=0; while true; do echo $i; i=$((i+1)); sleep 0.01; done'\nstdout: {}",
+            out.display()
+        ))
+        .unwrap();
+        let delay = time::Duration::from_millis(300);
+
+        drop(fs::remove_dir_all(&dir));
+        fs::create_dir_all(&dir).unwrap();
+        let mut monitor = Monitor::new(String::from("counter"), task.clone());
+        monitor.start_raw();
+        thread::sleep(delay);
+        let saved = monitor.release();
+        let child = &saved.children[0];
+        assert!(child.stdout.is_some() && child.stderr.is_some());
+        let released = counted(&out);
+
+        // the child keeps writing while no server reads its output
+        thread::sleep(delay);
+        let mut next = Monitor::new(String::from("counter"), task);
+        assert!(next.adopt(&saved, true));
+        thread::sleep(delay);
+        assert!(persist::is_alive(next.running[0].pid));
+        assert!(!next.running[0].adopted);
+        assert!(counted(&out) > released);
+        assert!(!next.tail().lock().unwrap().last(1).is_empty());
+
+        next.kill();
+        waitpid(Pid::from_raw(child.pid), None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_startable_state() {
         assert_eq!(startable_state(Status::Active), false);
         assert_eq!(startable_state(Status::Reloading), false);
         assert_eq!(startable_state(Status::Failing), false);
         assert_eq!(startable_state(Status::Stopping), false);
+        assert_eq!(startable_state(Status::Backoff), false);
+        assert_eq!(startable_state(Status::Unhealthy), false);
+        assert_eq!(startable_state(Status::Paused), false);
 
         assert_eq!(startable_state(Status::Reloaded), true);
         assert_eq!(startable_state(Status::Finished), true);
         assert_eq!(startable_state(Status::Inactive), true);
         assert_eq!(startable_state(Status::Failed), true);
         assert_eq!(startable_state(Status::Stopped), true);
+        assert_eq!(startable_state(Status::Fatal), true);
+        assert_eq!(startable_state(Status::Waiting), true);
     }
 
     #[test]
@@ -569,8 +1912,11 @@ mod monitor_suite {
 
         assert_eq!(finished_state(Status::Failing), Status::Failed);
         assert_eq!(finished_state(Status::Failed), Status::Failed);
+        assert_eq!(finished_state(Status::Unhealthy), Status::Failed);
 
         assert_eq!(finished_state(Status::Stopping), Status::Stopped);
         assert_eq!(finished_state(Status::Stopped), Status::Stopped);
+
+        assert_eq!(finished_state(Status::Fatal), Status::Fatal);
     }
 }
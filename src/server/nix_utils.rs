@@ -1,4 +1,6 @@
 use nix::{
+    errno::Errno,
+    libc,
     sys::{
         signal::Signal,
         stat::{mode_t, Mode},
@@ -22,8 +24,8 @@ pub enum NixError {
 impl Display for NixError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            NixError::InvalidMode(mode) => write!(f, "invalid mode {}", mode),
-            NixError::InvalidSignal(sig) => write!(f, "invalid signal {}", sig),
+            NixError::InvalidMode(mode) => write!(f, "invalid mode {mode}"),
+            NixError::InvalidSignal(sig) => write!(f, "invalid signal {sig}"),
         }
     }
 }
@@ -57,15 +59,7 @@ impl SerdeSignal {
         D: Deserializer<'de>,
     {
         let raw_signal = String::deserialize(deserializer)?;
-        let raw_sig = if raw_signal.starts_with("SIG") {
-            raw_signal.clone()
-        } else {
-            let mut raw_sig = String::from("SIG");
-            raw_sig.push_str(&raw_signal);
-            raw_sig
-        };
-        Signal::from_str(&raw_sig)
-            .map_err(|_| D::Error::custom(NixError::InvalidSignal(raw_signal)))
+        parse_signal(&raw_signal).map_err(D::Error::custom)
     }
 
     #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -73,10 +67,22 @@ impl SerdeSignal {
     where
         S: Serializer,
     {
-        String::serialize(&format!("{}", sig), serializer)
+        String::serialize(&format!("{sig}"), serializer)
     }
 }
 
+// parse_signal accept a signal name with or without its SIG prefix
+pub fn parse_signal(raw_signal: &str) -> Result<Signal, NixError> {
+    let raw_sig = if raw_signal.starts_with("SIG") {
+        raw_signal.to_string()
+    } else {
+        let mut raw_sig = String::from("SIG");
+        raw_sig.push_str(raw_signal);
+        raw_sig
+    };
+    Signal::from_str(&raw_sig).map_err(|_| NixError::InvalidSignal(raw_signal.to_string()))
+}
+
 pub trait UidGid {
     fn from_raw(id: u32) -> Self;
     fn to_raw(&self) -> u32;
@@ -118,6 +124,7 @@ where
         Ok(raw_id.map(T::from_raw))
     }
 
+    #[allow(clippy::ref_option)]
     pub fn serialize<S>(id: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -125,3 +132,10 @@ where
         id.as_ref().map(T::to_raw).serialize(serializer)
     }
 }
+
+// set_child_subreaper make the orphaned descendants of the server its children
+pub fn set_child_subreaper() -> nix::Result<()> {
+    let res = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+
+    Errno::result(res).map(drop)
+}
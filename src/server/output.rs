@@ -0,0 +1,439 @@
+use flate2::{write::GzEncoder, Compression};
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    poll::{poll, PollFd, PollFlags},
+    unistd::{pipe2, write},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{self, Display},
+    fs::{self, File, OpenOptions},
+    io::{self, prelude::*, ErrorKind},
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    thread::{self, JoinHandle},
+};
+
+use super::tail::{Lines, LogLine, SharedTail, Stream};
+
+const NULL: &str = "/dev/null";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    Append,
+    Truncate,
+}
+
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputMode::Append => write!(f, "append"),
+            OutputMode::Truncate => write!(f, "truncate"),
+        }
+    }
+}
+
+// A maxbytes of 0 disable the rotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputConfig {
+    pub path: PathBuf,
+    pub maxbytes: u64,
+    pub backups: u32,
+    pub mode: OutputMode,
+    pub gzip: bool,
+}
+
+impl OutputConfig {
+    fn is_null(&self) -> bool {
+        self.path == Path::new(NULL)
+    }
+
+    fn backup(&self, n: u32) -> PathBuf {
+        let ext = if self.gzip { ".gz" } else { "" };
+        let mut path = self.path.clone().into_os_string();
+
+        path.push(format!(".{n}{ext}"));
+        PathBuf::from(path)
+    }
+}
+
+#[derive(Debug)]
+pub struct RotatingFile {
+    config: OutputConfig,
+    file: File,
+    size: u64,
+    // the last rotated file being compressed, the backups are only shifted once done
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl RotatingFile {
+    pub fn open(config: OutputConfig) -> io::Result<Self> {
+        if config.mode == OutputMode::Truncate {
+            File::create(&config.path)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            config,
+            file,
+            size,
+            compressing: None,
+        })
+    }
+
+    // write split the buffer so no file grow past maxbytes, on a line boundary if possible
+    pub fn write(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let mut len = buf.len();
+
+            if self.config.maxbytes > 0 {
+                let room = self.config.maxbytes.saturating_sub(self.size);
+                let room = usize::try_from(room).unwrap_or(usize::MAX);
+
+                if len > room {
+                    match buf[..room].iter().rposition(|&c| c == b'\n') {
+                        Some(newline) => len = newline + 1,
+                        None if self.size > 0 => {
+                            self.rotate()?;
+                            continue;
+                        }
+                        None => len = room,
+                    }
+                }
+            }
+            self.file.write_all(&buf[..len])?;
+            self.size += len as u64;
+            buf = &buf[len..];
+        }
+        Ok(())
+    }
+
+    // rotate shift every backup by one, the oldest one is overwritten
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.config.backups == 0 {
+            self.file.set_len(0)?;
+            self.size = 0;
+            return Ok(());
+        }
+        self.wait_compression();
+        for n in (1..self.config.backups).rev() {
+            match fs::rename(self.config.backup(n), self.config.backup(n + 1)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+
+        let mut rotated = self.config.path.clone().into_os_string();
+        rotated.push(".1");
+        fs::rename(&self.config.path, &rotated)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)?;
+        self.size = 0;
+        if self.config.gzip {
+            self.compressing = Some(compress(PathBuf::from(rotated)));
+        }
+        Ok(())
+    }
+
+    fn wait_compression(&mut self) {
+        if let Some(compressing) = self.compressing.take() {
+            drop(compressing.join());
+        }
+    }
+}
+
+// compress gzip a rotated file in the background, the output being written meanwhile
+fn compress(path: PathBuf) -> JoinHandle<()> {
+    thread::spawn(move || {
+        if let Err(e) = gzip(&path) {
+            log::warn!("cannot compress {}: {e}", path.display());
+        }
+    })
+}
+
+// gzip replace the file at path by path.gz, like the gzip program
+fn gzip(path: &Path) -> io::Result<()> {
+    let mut gz = path.to_path_buf().into_os_string();
+
+    gz.push(".gz");
+    let mut encoder = GzEncoder::new(File::create(gz)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+pub type SharedOutput = Arc<Mutex<RotatingFile>>;
+
+// Handover stops the forwarding of the children outputs, so their pipes can be
+// passed to the next server with what is left unread
+#[derive(Debug)]
+struct Handover {
+    stopped: Arc<OwnedFd>,
+    stop: OwnedFd,
+}
+
+impl Handover {
+    fn new() -> nix::Result<Self> {
+        let (read, write) = pipe2(OFlag::O_CLOEXEC)?;
+
+        // the fds given by the os are owned by nobody else
+        unsafe {
+            Ok(Self {
+                stopped: Arc::new(OwnedFd::from_raw_fd(read)),
+                stop: OwnedFd::from_raw_fd(write),
+            })
+        }
+    }
+}
+
+// Children writing to the same path share the same file while one of them is alive
+#[derive(Debug, Default)]
+pub struct Outputs {
+    files: HashMap<PathBuf, Weak<Mutex<RotatingFile>>>,
+    handover: Option<Handover>,
+}
+
+impl Outputs {
+    pub fn open(&mut self, config: &OutputConfig) -> Option<SharedOutput> {
+        if config.is_null() {
+            return None;
+        }
+        self.files.retain(|_, file| file.strong_count() > 0);
+        if let Some(file) = self.files.get(&config.path).and_then(Weak::upgrade) {
+            return Some(file);
+        }
+        match RotatingFile::open(config.clone()) {
+            Ok(file) => {
+                let file = Arc::new(Mutex::new(file));
+                self.files
+                    .insert(config.path.clone(), Arc::downgrade(&file));
+                Some(file)
+            }
+            Err(e) => {
+                log::error!("cannot open {}: {}", config.path.display(), e);
+                None
+            }
+        }
+    }
+
+    // forward copy everything read from a child pipe to its sink, until the pipe
+    // is closed or the forwarding stopped, the pipe being given back then
+    pub fn forward<R>(&mut self, reader: R, sink: Sink) -> JoinHandle<Option<OwnedFd>>
+    where
+        R: Read + AsRawFd + Into<OwnedFd> + Send + 'static,
+    {
+        if self.handover.is_none() {
+            match Handover::new() {
+                Ok(handover) => self.handover = Some(handover),
+                Err(e) => log::error!("cannot pipe, the outputs can't be handed over: {e}"),
+            }
+        }
+        let stopped = self.handover.as_ref().map(|h| Arc::clone(&h.stopped));
+
+        thread::spawn(move || forward(reader, &sink, stopped.as_deref()))
+    }
+
+    // stop_forwarding stop every forwarding thread once it wrote what it read,
+    // the next ones get a new handover
+    pub fn stop_forwarding(&mut self) {
+        if let Some(handover) = self.handover.take() {
+            if let Err(e) = write(handover.stop.as_raw_fd(), b"x") {
+                log::error!("cannot stop the output forwarding: {e}");
+            }
+        }
+    }
+}
+
+// Sink is where one stream of a child goes: its output file and the task tail
+pub struct Sink {
+    pub output: Option<SharedOutput>,
+    pub tail: SharedTail,
+    pub instance: u32,
+    pub stream: Stream,
+}
+
+impl Sink {
+    fn write(&self, buf: &[u8]) {
+        if let Some(output) = &self.output {
+            let mut file = output.lock().unwrap();
+            if let Err(e) = file.write(buf) {
+                log::error!("cannot write to {}: {}", file.config.path.display(), e);
+            }
+        }
+    }
+
+    fn push(&self, line: String) {
+        self.tail.lock().unwrap().push(LogLine {
+            instance: self.instance,
+            stream: self.stream,
+            line,
+        });
+    }
+}
+
+// stopped is readable once the forwarding is stopped, it is never read
+fn forward<R>(mut reader: R, sink: &Sink, stopped: Option<&OwnedFd>) -> Option<OwnedFd>
+where
+    R: Read + AsRawFd + Into<OwnedFd>,
+{
+    let mut buf = [0_u8; 4096];
+    let mut lines = Lines::default();
+
+    loop {
+        if let Some(stopped) = stopped {
+            let mut fds = [
+                PollFd::new(reader.as_raw_fd(), PollFlags::POLLIN),
+                PollFd::new(stopped.as_raw_fd(), PollFlags::POLLIN),
+            ];
+
+            match poll(&mut fds, -1) {
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                Err(e) => {
+                    log::error!("cannot poll child output: {e}");
+                    break;
+                }
+                Ok(_) if fds[1].revents().is_some_and(|r| !r.is_empty()) => {
+                    return Some(reader.into());
+                }
+                Ok(_) => {}
+            }
+        }
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                sink.write(&buf[..n]);
+                lines.feed(&buf[..n]).into_iter().for_each(|l| sink.push(l));
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => {
+                log::error!("cannot read child output: {e}");
+                break;
+            }
+        }
+    }
+    if let Some(line) = lines.flush() {
+        sink.push(line);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test_output {
+    use super::{OutputConfig, OutputMode, Outputs, RotatingFile};
+    use flate2::read::GzDecoder;
+    use std::{fs, fs::File, io::Read, path::PathBuf, sync::Arc};
+    use tempfile::TempDir;
+
+    // config write in a temporary directory, removed when it is dropped
+    fn config(mode: OutputMode) -> (TempDir, OutputConfig) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = OutputConfig {
+            path: dir.path().join("out.log"),
+            maxbytes: 10,
+            backups: 2,
+            mode,
+            gzip: false,
+        };
+
+        (dir, config)
+    }
+
+    #[test]
+    fn test_rotation() {
+        let (_dir, config) = config(OutputMode::Truncate);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        for chunk in &["0123456789", "abcdefghij", "ABCDEFGHIJ", "xyz"] {
+            file.write(chunk.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&config.path).unwrap(), "xyz");
+        assert_eq!(fs::read_to_string(config.backup(1)).unwrap(), "ABCDEFGHIJ");
+        assert_eq!(fs::read_to_string(config.backup(2)).unwrap(), "abcdefghij");
+        assert!(!config.backup(3).exists());
+    }
+
+    #[test]
+    fn test_rotation_split() {
+        let (_dir, config) = config(OutputMode::Truncate);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        file.write(b"foo\nbar\nbaz\n0123456789abc").unwrap();
+        assert_eq!(fs::read_to_string(&config.path).unwrap(), "abc");
+        assert_eq!(fs::read_to_string(config.backup(1)).unwrap(), "0123456789");
+        assert_eq!(fs::read_to_string(config.backup(2)).unwrap(), "baz\n");
+        assert!(!config.backup(3).exists());
+    }
+
+    #[test]
+    fn test_rotation_gzip() {
+        let (_dir, config) = config(OutputMode::Truncate);
+        let config = OutputConfig {
+            gzip: true,
+            ..config
+        };
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        for chunk in &["0123456789", "abcdefghij", "xyz"] {
+            file.write(chunk.as_bytes()).unwrap();
+        }
+        file.wait_compression();
+        assert_eq!(fs::read_to_string(&config.path).unwrap(), "xyz");
+        assert!(config.backup(1).exists());
+        assert!(config.backup(2).exists());
+        assert!(!config.path.with_extension("log.1").exists());
+        let mut backup = String::new();
+        GzDecoder::new(File::open(config.backup(1)).unwrap())
+            .read_to_string(&mut backup)
+            .unwrap();
+        assert_eq!(backup, "abcdefghij");
+    }
+
+    #[test]
+    fn test_mode() {
+        let (_dir, config) = config(OutputMode::Append);
+        fs::write(&config.path, "foo").unwrap();
+
+        RotatingFile::open(config.clone())
+            .unwrap()
+            .write(b"bar")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&config.path).unwrap(), "foobar");
+
+        let config = OutputConfig {
+            mode: OutputMode::Truncate,
+            ..config
+        };
+        RotatingFile::open(config.clone())
+            .unwrap()
+            .write(b"baz")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&config.path).unwrap(), "baz");
+    }
+
+    #[test]
+    fn test_shared_outputs() {
+        let (_dir, config) = config(OutputMode::Truncate);
+        let mut outputs = Outputs::default();
+
+        let first = outputs.open(&config).unwrap();
+        let second = outputs.open(&config).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let null = OutputConfig {
+            path: PathBuf::from("/dev/null"),
+            ..config
+        };
+        assert!(outputs.open(&null).is_none());
+    }
+}
@@ -0,0 +1,214 @@
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, FdFlag},
+    sys::signal::kill,
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    path::PathBuf,
+};
+
+// SavedChild is a running child, as remembered by the state file
+// started_on is a unix timestamp, starttime is in clock ticks since boot
+// stdout and stderr are the fds of its output pipes, inherited through a handover
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedChild {
+    pub pid: i32,
+    pub instance: u32,
+    pub started_on: u64,
+    pub starttime: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<RawFd>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<RawFd>,
+}
+
+impl SavedChild {
+    pub fn new(pid: Pid, instance: u32, started_on: u64) -> Self {
+        Self {
+            pid: pid.as_raw(),
+            instance,
+            started_on,
+            starttime: starttime(pid),
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    // pipes take back the output pipes passed by the previous server, they are
+    // only valid in the process it handed over to
+    pub fn pipes(&self) -> (Option<OwnedFd>, Option<OwnedFd>) {
+        (self.stdout.map(inherited), self.stderr.map(inherited))
+    }
+
+    // is_alive is false once the pid is gone, or was reused by another process
+    pub fn is_alive(&self) -> bool {
+        let pid = Pid::from_raw(self.pid);
+
+        is_alive(pid)
+            && match (self.starttime, starttime(pid)) {
+                (Some(saved), Some(current)) => saved == current,
+                _ => true,
+            }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedMonitor {
+    pub spawned_children: u32,
+    pub retry_count: u32,
+    pub children: Vec<SavedChild>,
+    // the unix timestamp of the last scheduled run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<u64>,
+}
+
+pub type Saved = BTreeMap<String, SavedMonitor>;
+
+// StateFile keep what a new server needs to adopt the children left running
+#[derive(Debug)]
+pub struct StateFile {
+    path: PathBuf,
+    written: String,
+}
+
+impl StateFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            written: String::new(),
+        }
+    }
+
+    // load read the previous state, a missing or invalid file is an empty one
+    pub fn load(&self) -> Saved {
+        let raw = match fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Saved::new(),
+            Err(e) => {
+                log::error!("cannot read state file {}: {}", self.path.display(), e);
+                return Saved::new();
+            }
+        };
+
+        serde_json::from_str(&raw).unwrap_or_else(|e| {
+            log::error!("invalid state file {}: {}", self.path.display(), e);
+            Saved::new()
+        })
+    }
+
+    // save replace the file, only when the state changed since the last save
+    pub fn save(&mut self, saved: &Saved) -> io::Result<()> {
+        let raw = serde_json::to_string_pretty(saved)?;
+        if raw == self.written {
+            return Ok(());
+        }
+        let mut tmp = self.path.clone().into_os_string();
+
+        tmp.push(".tmp");
+        fs::write(&tmp, &raw)?;
+        fs::rename(&tmp, &self.path)?;
+        self.written = raw;
+        Ok(())
+    }
+}
+
+// handed_over keep a pipe open across the exec of the next server
+pub fn handed_over(pipe: OwnedFd) -> nix::Result<RawFd> {
+    fcntl(pipe.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::empty()))?;
+    Ok(pipe.into_raw_fd())
+}
+
+// inherited fds are owned by nobody else, they are closed again on exec
+fn inherited(fd: RawFd) -> OwnedFd {
+    let pipe = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    if let Err(e) = fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)) {
+        log::warn!("cannot set close-on-exec on fd {fd}: {e}");
+    }
+    pipe
+}
+
+// a process owned by another user is alive too
+pub fn is_alive(pid: Pid) -> bool {
+    !matches!(kill(pid, None), Err(nix::Error::Sys(Errno::ESRCH)))
+}
+
+// starttime tell a process from a later one reusing its pid
+fn starttime(pid: Pid) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    parse_starttime(&stat)
+}
+
+// the command name may hold spaces, the fields after it are counted from the state, the 3rd
+fn parse_starttime(stat: &str) -> Option<u64> {
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+#[cfg(test)]
+mod test_persist {
+    use super::{parse_starttime, Saved, SavedChild, SavedMonitor, StateFile};
+    use nix::unistd::Pid;
+    use std::{convert::TryFrom, env, fs, process};
+
+    #[test]
+    fn test_parse_starttime() {
+        let stat =
+            "42 (my (odd) cmd) S 1 42 42 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 123456 1000 10";
+
+        assert_eq!(parse_starttime(stat), Some(123_456));
+        assert_eq!(parse_starttime("42 (cmd) S 1"), None);
+        assert_eq!(parse_starttime("garbage"), None);
+    }
+
+    #[test]
+    fn test_is_alive() {
+        let pid = Pid::from_raw(i32::try_from(process::id()).unwrap());
+        let mut child = SavedChild::new(pid, 0, 0);
+
+        assert!(child.is_alive());
+        if let Some(starttime) = child.starttime {
+            child.starttime = Some(starttime + 1);
+            assert!(!child.is_alive());
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = env::temp_dir().join(format!("taskmaster-state-{}.json", process::id()));
+        let mut file = StateFile::new(path.clone());
+        let mut saved = Saved::new();
+
+        assert_eq!(file.load(), Saved::new());
+        saved.insert(
+            String::from("web"),
+            SavedMonitor {
+                spawned_children: 3,
+                retry_count: 1,
+                children: vec![SavedChild {
+                    pid: 42,
+                    instance: 2,
+                    started_on: 1_600_000_000,
+                    starttime: None,
+                    stdout: Some(5),
+                    stderr: None,
+                }],
+                last_run: Some(1_600_000_060),
+            },
+        );
+        file.save(&saved).unwrap();
+        assert_eq!(file.load(), saved);
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(file.load(), Saved::new());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+use nix::{
+    errno::Errno,
+    sys::wait::{waitpid, WaitPidFlag, WaitStatus},
+    unistd::Pid,
+};
+use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+// reap collect every terminated child without blocking
+pub fn reap() -> Vec<(Pid, ExitStatus)> {
+    let mut reaped = Vec::new();
+
+    loop {
+        match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::StillAlive) | Err(nix::Error::Sys(Errno::ECHILD)) => break,
+            Ok(status) => {
+                if let Some(exited) = exit_status(status) {
+                    reaped.push(exited);
+                }
+            }
+            Err(e) => {
+                log::error!("cannot wait for children: {e}");
+                break;
+            }
+        }
+    }
+    reaped
+}
+
+fn exit_status(status: WaitStatus) -> Option<(Pid, ExitStatus)> {
+    match status {
+        WaitStatus::Exited(pid, code) => Some((pid, ExitStatus::from_raw(code << 8))),
+        WaitStatus::Signaled(pid, signal, core_dumped) => {
+            let core_flag = if core_dumped { 0x80 } else { 0 };
+            Some((pid, ExitStatus::from_raw(signal as i32 | core_flag)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_reaper {
+    use super::exit_status;
+    use nix::{
+        sys::{signal::Signal, wait::WaitStatus},
+        unistd::Pid,
+    };
+    use std::os::unix::process::ExitStatusExt;
+
+    #[test]
+    fn test_exit_status() {
+        let pid = Pid::from_raw(42);
+
+        let (got_pid, status) = exit_status(WaitStatus::Exited(pid, 3)).unwrap();
+        assert_eq!(got_pid, pid);
+        assert_eq!(status.code(), Some(3));
+
+        let (_, status) = exit_status(WaitStatus::Signaled(pid, Signal::SIGKILL, false)).unwrap();
+        assert_eq!(status.code(), None);
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+
+        let (_, status) = exit_status(WaitStatus::Signaled(pid, Signal::SIGSEGV, true)).unwrap();
+        assert_eq!(status.signal(), Some(Signal::SIGSEGV as i32));
+        assert!(status.core_dumped());
+
+        assert!(exit_status(WaitStatus::StillAlive).is_none());
+    }
+}
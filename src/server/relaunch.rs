@@ -16,6 +16,6 @@ impl Display for Relaunch {
             Relaunch::OnError => "on-error",
             Relaunch::Never => "never",
         };
-        write!(f, "{}", s)
+        write!(f, "{s}")
     }
 }
@@ -0,0 +1,335 @@
+use nix::libc;
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+    mem, ptr,
+};
+
+use super::default;
+
+// Bounds of each cron field: minute, hour, day of month, month, day of week
+const FIELDS: [(&str, u32, u32); 5] = [
+    ("minute", 0, 59),
+    ("hour", 0, 23),
+    ("day of month", 1, 31),
+    ("month", 1, 12),
+    ("day of week", 0, 7),
+];
+
+// How many fields of the local time are tried before giving up on a
+// cron expression that never matches, like the 31st of February
+const MAX_STEPS: usize = 10_000;
+
+// When is how runs are spaced: a cron expression in local time, or a fixed
+// number of seconds after the previous run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum When {
+    Cron(Cron),
+    Every(u32),
+}
+
+impl Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            When::Cron(cron) => write!(f, "cron {:?}", cron.expression),
+            When::Every(secs) => write!(f, "every {secs}s"),
+        }
+    }
+}
+
+// Overlap is what a run due while the previous one is still running does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overlap {
+    Skip,
+    Queue,
+    Kill,
+}
+
+impl Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Overlap::Skip => "skip",
+            Overlap::Queue => "queue",
+            Overlap::Kill => "kill",
+        };
+        write!(f, "{s}")
+    }
+}
+
+// Missed is what the runs due while the server was down do once it is back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Missed {
+    Skip,
+    RunOnce,
+}
+
+impl Display for Missed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Missed::Skip => "skip",
+            Missed::RunOnce => "run-once",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(flatten)]
+    pub when: When,
+
+    #[serde(default = "default::overlap")]
+    pub overlap: Overlap,
+
+    #[serde(default = "default::missed")]
+    pub missed: Missed,
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, overlap: {}, missed: {}",
+            self.when, self.overlap, self.missed
+        )
+    }
+}
+
+impl Schedule {
+    // next_after is the first run strictly after a unix timestamp, if any
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        match &self.when {
+            When::Cron(cron) => cron.next_after(after),
+            When::Every(secs) => Some(after + u64::from(*secs.max(&1))),
+        }
+    }
+}
+
+// Cron is a five fields expression, each field a set of bits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    expression: String,
+    fields: [u64; 5],
+    // with both days restricted, either of them matching is enough
+    days_restricted: bool,
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, String> {
+        let expanded = match expression.as_str() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let specs: Vec<&str> = expanded.split_whitespace().collect();
+
+        if specs.len() != FIELDS.len() {
+            return Err(format!(
+                "{expression:?}: expected 5 fields, minute hour day-of-month month day-of-week"
+            ));
+        }
+        let mut fields = [0; 5];
+        for (i, (spec, (name, min, max))) in specs.iter().zip(FIELDS.iter()).enumerate() {
+            fields[i] = parse_field(spec, *min, *max)
+                .map_err(|e| format!("{expression:?}: invalid {name} {spec:?}: {e}"))?;
+        }
+        // sunday is either 0 or 7
+        if fields[4] & 1 << 7 != 0 {
+            fields[4] |= 1;
+        }
+
+        Ok(Self {
+            days_restricted: !specs[2].starts_with('*') && !specs[4].starts_with('*'),
+            expression,
+            fields,
+        })
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> String {
+        cron.expression
+    }
+}
+
+// parse_field turn a field like "1-5,*/15" into the set of values it matches
+fn parse_field(spec: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_value(step, 1, max)?),
+            None => (part, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (parse_value(first, min, max)?, parse_value(last, min, max)?),
+            // "5/10" starts at 5 and goes on until the end
+            None if step > 1 => (parse_value(range, min, max)?, max),
+            None => {
+                let value = parse_value(range, min, max)?;
+                (value, value)
+            }
+        };
+
+        if first > last {
+            return Err(format!("{first} is after {last}"));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!("{value:?} is not in {min}..={max}")),
+    }
+}
+
+fn has(bits: u64, value: libc::c_int) -> bool {
+    u32::try_from(value).is_ok_and(|value| value < 64 && bits & 1 << value != 0)
+}
+
+impl Cron {
+    // next_after walk the local time forward, skipping a whole month, day
+    // or hour as soon as it doesn't match
+    fn next_after(&self, after: u64) -> Option<u64> {
+        let [minutes, hours, days, months, weekdays] = self.fields;
+        let mut tm = localtime(libc::time_t::try_from(after).ok()?)?;
+
+        tm.tm_sec = 0;
+        tm.tm_min += 1;
+        for _ in 0..MAX_STEPS {
+            let time = normalize(&mut tm)?;
+            let day = if self.days_restricted {
+                has(days, tm.tm_mday) || has(weekdays, tm.tm_wday)
+            } else {
+                has(days, tm.tm_mday) && has(weekdays, tm.tm_wday)
+            };
+
+            if !has(months, tm.tm_mon + 1) {
+                tm.tm_mon += 1;
+                tm.tm_mday = 1;
+                tm.tm_hour = 0;
+                tm.tm_min = 0;
+            } else if !day {
+                tm.tm_mday += 1;
+                tm.tm_hour = 0;
+                tm.tm_min = 0;
+            } else if !has(hours, tm.tm_hour) {
+                tm.tm_hour += 1;
+                tm.tm_min = 0;
+            } else if !has(minutes, tm.tm_min) {
+                tm.tm_min += 1;
+            } else {
+                return u64::try_from(time).ok().filter(|time| *time > after);
+            }
+        }
+        None
+    }
+}
+
+fn localtime(time: libc::time_t) -> Option<libc::tm> {
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+
+    if unsafe { libc::localtime_r(ptr::addr_of!(time), ptr::addr_of_mut!(tm)) }.is_null() {
+        None
+    } else {
+        Some(tm)
+    }
+}
+
+// normalize carry the fields over, the daylight saving time being found again
+fn normalize(tm: &mut libc::tm) -> Option<libc::time_t> {
+    tm.tm_isdst = -1;
+    match unsafe { libc::mktime(tm) } {
+        -1 => None,
+        time => Some(time),
+    }
+}
+
+#[cfg(test)]
+mod test_schedule {
+    use super::{normalize, Cron, Missed, Overlap, Schedule, When};
+    use nix::libc;
+    use std::{convert::TryFrom, mem};
+
+    // local is a unix timestamp of a local time, far from daylight saving changes
+    fn local(year: i32, month: i32, day: i32, hour: i32, min: i32) -> u64 {
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+        tm.tm_hour = hour;
+        tm.tm_min = min;
+        u64::try_from(normalize(&mut tm).unwrap()).unwrap()
+    }
+
+    fn next(expression: &str, after: u64) -> Option<u64> {
+        Cron::try_from(String::from(expression))
+            .unwrap()
+            .next_after(after)
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        assert!(Cron::try_from(String::from("*/5 * * * *")).is_ok());
+        assert!(Cron::try_from(String::from("0 9-17/2 1,15 * 1-5")).is_ok());
+        assert!(Cron::try_from(String::from("@daily")).is_ok());
+        assert!(Cron::try_from(String::from("* * * *")).is_err());
+        assert!(Cron::try_from(String::from("60 * * * *")).is_err());
+        assert!(Cron::try_from(String::from("5-1 * * * *")).is_err());
+        assert!(Cron::try_from(String::from("*/0 * * * *")).is_err());
+    }
+
+    #[test]
+    fn test_next_cron() {
+        let start = local(2024, 1, 10, 10, 3) + 20;
+
+        assert_eq!(next("* * * * *", start), Some(local(2024, 1, 10, 10, 4)));
+        assert_eq!(next("*/5 * * * *", start), Some(local(2024, 1, 10, 10, 5)));
+        assert_eq!(next("3 * * * *", start), Some(local(2024, 1, 10, 11, 3)));
+        assert_eq!(next("@daily", start), Some(local(2024, 1, 11, 0, 0)));
+        assert_eq!(next("30 8 1 * *", start), Some(local(2024, 2, 1, 8, 30)));
+        assert_eq!(next("0 0 29 2 *", start), Some(local(2024, 2, 29, 0, 0)));
+        // 2024-01-10 is a wednesday
+        assert_eq!(next("0 12 * * 5", start), Some(local(2024, 1, 12, 12, 0)));
+        assert_eq!(next("0 12 * * 7", start), Some(local(2024, 1, 14, 12, 0)));
+        assert_eq!(next("0 12 20 * 5", start), Some(local(2024, 1, 12, 12, 0)));
+        assert_eq!(next("0 0 31 2 *", start), None);
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule: Schedule = serde_yaml::from_str("every: 30\noverlap: queue").unwrap();
+        assert_eq!(schedule.when, When::Every(30));
+        assert_eq!(schedule.overlap, Overlap::Queue);
+        assert_eq!(schedule.missed, Missed::Skip);
+        assert_eq!(schedule.next_after(100), Some(130));
+
+        let schedule: Schedule =
+            toml::from_str("cron = \"0 3 * * *\"\nmissed = \"run-once\"").unwrap();
+        assert!(matches!(schedule.when, When::Cron(_)));
+        assert_eq!(schedule.missed, Missed::RunOnce);
+        assert_eq!(
+            schedule.to_string(),
+            "cron \"0 3 * * *\", overlap: skip, missed: run-once"
+        );
+
+        assert!(serde_yaml::from_str::<Schedule>("cron: '* *'").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use regex::Regex;
+
+use crate::shared::message::GROUP_PREFIX;
+
+// Selector is how a client designates tasks: a name, a group:<name>, a glob
+// like worker-*, a /regex/, or all
+#[derive(Debug)]
+pub enum Selector {
+    Task(String),
+    Group(String),
+    Pattern(Regex),
+}
+
+impl Selector {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        if let Some(group) = id.strip_prefix(GROUP_PREFIX) {
+            return Ok(Selector::Group(group.to_string()));
+        }
+        let pattern = if id == "all" {
+            String::new()
+        } else if let Some(regex) = id
+            .strip_prefix('/')
+            .and_then(|regex| regex.strip_suffix('/'))
+        {
+            regex.to_string()
+        } else if id.contains(['*', '?', '[']) {
+            glob_to_regex(id)
+        } else {
+            return Ok(Selector::Task(id.to_string()));
+        };
+
+        Regex::new(&pattern)
+            .map(Selector::Pattern)
+            .map_err(|e| format!("invalid selector {id}: {e}"))
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+            }
+            ']' => regex.push(']'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod test_selector {
+    use super::Selector;
+
+    fn matches(id: &str, name: &str) -> bool {
+        match Selector::parse(id).unwrap() {
+            Selector::Pattern(regex) => regex.is_match(name),
+            selector => panic!("{} parsed as {:?}", id, selector),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(Selector::parse("web"), Ok(Selector::Task(name)) if name == "web"));
+        assert!(matches!(Selector::parse("group:web"), Ok(Selector::Group(name)) if name == "web"));
+        assert!(Selector::parse("/(/").is_err());
+        assert!(Selector::parse("worker-[1").is_err());
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(matches("worker-*", "worker-1"));
+        assert!(!matches("worker-*", "api-worker-1"));
+        assert!(matches("worker-?", "worker-2"));
+        assert!(!matches("worker-?", "worker-12"));
+        assert!(matches("worker-[12]", "worker-2"));
+        assert!(!matches("worker-[!12]", "worker-2"));
+        assert!(!matches("a.b*", "axb"));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(matches("/^api-/", "api-1"));
+        assert!(!matches("/^api-/", "web-api-1"));
+        assert!(matches("all", "anything"));
+    }
+}
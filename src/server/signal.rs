@@ -1,5 +1,5 @@
 use signal_hook::{
-    consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM},
+    consts::{SIGCHLD, SIGHUP, SIGINT, SIGQUIT, SIGTERM},
     iterator::Signals,
 };
 use std::sync::mpsc::Sender;
@@ -13,11 +13,12 @@ fn sigstr(signum: i32) -> &'static str {
         SIGTERM => "SIGTERM",
         SIGHUP => "SIGHUP",
         SIGQUIT => "SIGQUIT",
+        SIGCHLD => "SIGCHLD",
         _ => "unknown",
     }
 }
 pub fn handle_signals(sender: Sender<Inter>) -> Result<(), error::Taskmaster> {
-    let mut watching_signals = match Signals::new(&[SIGHUP, SIGINT, SIGTERM, SIGQUIT]) {
+    let mut watching_signals = match Signals::new([SIGHUP, SIGINT, SIGTERM, SIGQUIT, SIGCHLD]) {
         Ok(c) => c,
         Err(e) => return Err(error::Taskmaster::Io(e)),
     };
@@ -26,17 +27,22 @@ pub fn handle_signals(sender: Sender<Inter>) -> Result<(), error::Taskmaster> {
             match sig {
                 SIGHUP => {
                     log::debug!("received SIGHUP, send Reload message");
-                    sender.send(Inter::Reload).unwrap()
+                    sender.send(Inter::Reload).unwrap();
+                }
+                SIGCHLD => {
+                    log::debug!("received SIGCHLD, sending Reap message");
+                    // the server may already be gone while its children are killed
+                    drop(sender.send(Inter::Reap));
                 }
                 SIGINT | SIGTERM | SIGQUIT => {
                     log::debug!("received {}, sending Quit message", sigstr(sig));
-                    sender.send(Inter::Quit).unwrap()
+                    sender.send(Inter::Quit).unwrap();
                 }
                 _ => {
-                    log::error!("unhandled signal value {}", sig);
+                    log::error!("unhandled signal value {sig}");
                     unreachable!()
                 }
-            };
+            }
         }
     });
     Ok(())
@@ -45,7 +51,7 @@ pub fn handle_signals(sender: Sender<Inter>) -> Result<(), error::Taskmaster> {
 #[cfg(test)]
 mod test_signal {
     use super::sigstr;
-    use signal_hook::consts::{SIGCONT, SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+    use signal_hook::consts::{SIGCHLD, SIGCONT, SIGHUP, SIGINT, SIGQUIT, SIGTERM};
 
     #[test]
     fn test_sigstr() {
@@ -53,6 +59,7 @@ mod test_signal {
         assert_eq!(sigstr(SIGQUIT), "SIGQUIT");
         assert_eq!(sigstr(SIGINT), "SIGINT");
         assert_eq!(sigstr(SIGHUP), "SIGHUP");
+        assert_eq!(sigstr(SIGCHLD), "SIGCHLD");
         assert_eq!(sigstr(SIGCONT), "unknown");
     }
 }
@@ -1,195 +1,613 @@
+use nix::unistd::Uid;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::TryFrom,
-    marker,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc::Sender,
-        Arc, Mutex,
-    },
-    thread::{self, JoinHandle},
+    marker, slice,
+    sync::mpsc::Sender,
     time,
 };
 
 use super::{
+    access::{self, AccessRule, Peer},
     communication::Com,
+    diff::ReloadDiff,
+    error,
     formatter::Formatter,
     inter::Inter,
-    monitor::Monitor,
+    monitor::{Monitor, Status},
+    nix_utils,
+    persist::{Saved, StateFile},
+    reaper,
+    selector::Selector,
     task::{ConfigFile, Task},
     watcher::Watcher,
 };
+use crate::shared::message::Message;
+
+// Number of recent lines sent before following a task
+const FOLLOW_BACKLOG: usize = 10;
+
+// Target is what a task id designates: one task, or the tasks matched by a selector
+enum Target {
+    Task(String),
+    Tasks(String, Vec<String>),
+}
+
+impl Target {
+    fn tasks(&self) -> &[String] {
+        match self {
+            Target::Task(name) => slice::from_ref(name),
+            Target::Tasks(_, tasks) => tasks,
+        }
+    }
+
+    fn is_selection(&self) -> bool {
+        matches!(self, Target::Tasks(..))
+    }
+}
 
 #[derive(Debug)]
 pub struct State<F>
 where
     F: Formatter,
 {
-    pub monitors: Arc<Mutex<HashMap<String, Monitor>>>,
-    sender: Sender<Inter>,
-    response: Sender<Com>,
-    thread: Option<JoinHandle<()>>,
-    waiter_running: Arc<AtomicBool>,
+    pub monitors: HashMap<String, Monitor>,
+    order: Vec<String>,
+    groups: BTreeMap<String, Vec<String>>,
+    access: Vec<AccessRule>,
+    shutting_down: bool,
+    event: Sender<Inter>,
+    persisted: Option<StateFile>,
+    // the children left by a previous server, adopted on the first reload
+    saved: Saved,
+    // the previous server handed over to this one, its children are ours
+    handed_over: bool,
     _marker: marker::PhantomData<F>,
 }
 
 impl<F: Formatter> State<F> {
-    pub fn new(sender: Sender<Inter>, response: Sender<Com>) -> Self {
+    pub fn new(event: Sender<Inter>, persisted: Option<StateFile>, handed_over: bool) -> Self {
+        let saved = persisted.as_ref().map(StateFile::load).unwrap_or_default();
+
         Self {
-            monitors: Arc::new(Mutex::new(HashMap::new())),
-            sender,
-            response,
-            thread: None,
-            waiter_running: Arc::new(AtomicBool::new(false)),
+            monitors: HashMap::new(),
+            order: Vec::new(),
+            groups: BTreeMap::new(),
+            access: Vec::new(),
+            shutting_down: false,
+            event,
+            persisted,
+            saved,
+            handed_over,
             _marker: marker::PhantomData,
         }
     }
 
-    pub fn reload(&mut self, watcher: &Watcher) {
-        let configfile: ConfigFile = ConfigFile::try_from(watcher).unwrap();
-        let mut to_remove: Vec<String> = self.monitors.lock().unwrap().keys().cloned().collect();
+    pub fn reload(&mut self, watcher: &Watcher) -> Result<(), error::Taskmaster> {
+        let mut configfile: ConfigFile = ConfigFile::try_from(watcher)?;
+        let mut to_remove: Vec<String> = self.monitors.keys().cloned().collect();
+        let mut added = Vec::new();
+        let mut reloaded = Vec::new();
 
-        for (name, task) in configfile {
-            log::debug!("parsed task: {}: {:?}", name, task);
+        self.access = configfile.access;
+        self.groups = configfile.groups;
+        for name in &configfile.order {
+            let task = configfile.tasks.remove(name).unwrap();
+            log::debug!("parsed task: {name}: {task:?}");
 
-            if self.monitors.lock().unwrap().get(&name).is_some() {
-                to_remove.retain(|taskid| taskid != &name);
-                self.may_reload_task(&name, task);
+            if self.monitors.contains_key(name) {
+                to_remove.retain(|taskid| taskid != name);
+                if self.may_reload_task(name, task) {
+                    reloaded.push(name);
+                }
             } else {
-                self.add_task(&name, task);
+                self.add_task(name, task);
+                added.push(name);
             }
         }
-        let mut monitors = self.monitors.lock().unwrap();
-        log::info!("removed task from config: {:?}", to_remove);
+        log::info!("removed task from config: {to_remove:?}");
         while !to_remove.is_empty() {
             let taskid = to_remove.remove(0);
-            monitors.remove(&taskid);
+            self.monitors.remove(&taskid);
+        }
+        // once every task is reloaded, so the dependencies restarted are waited for
+        for name in reloaded {
+            self.start_task(name);
         }
+        for name in added {
+            if self.adopt(name) {
+                continue;
+            }
+            if self.monitors[name].get_task().autostart {
+                self.start_task(name);
+            }
+        }
+        for (name, saved) in &self.saved {
+            for child in &saved.children {
+                // nobody reads its output anymore
+                if self.handed_over {
+                    drop(child.pipes());
+                }
+                if child.is_alive() {
+                    log::warn!(
+                        "[{}] not configured anymore, child-{} left running",
+                        name,
+                        child.pid
+                    );
+                }
+            }
+        }
+        self.saved.clear();
+        self.order = configfile.order;
+        Ok(())
+    }
+
+    // adopt hand the children a previous server left running to their task
+    fn adopt(&mut self, name: &str) -> bool {
+        match self.saved.remove(name) {
+            Some(saved) => self
+                .monitors
+                .get_mut(name)
+                .unwrap()
+                .adopt(&saved, self.handed_over),
+            None => false,
+        }
+    }
+
+    // persist save the running children, for a new server to adopt them
+    pub fn persist(&mut self) {
+        // the children of the previous server are not adopted yet
+        if !self.saved.is_empty() {
+            return;
+        }
+        let saved = self
+            .monitors
+            .iter()
+            .map(|(name, manager)| (name.clone(), manager.saved()))
+            .collect();
+
+        self.save(&saved);
     }
 
-    fn may_reload_task(&mut self, name: &str, task: Task) {
-        let mut monitors = self.monitors.lock().unwrap();
-        let mon = monitors.get_mut(name).unwrap();
+    fn save(&mut self, saved: &Saved) {
+        let Some(persisted) = &mut self.persisted else {
+            return;
+        };
+        if let Err(e) = persisted.save(saved) {
+            log::error!("cannot save the state file: {e}");
+        }
+    }
+
+    // can_release is false without a state file, a handover couldn't pass the tasks on
+    pub fn can_release(&self) -> bool {
+        self.persisted.is_some()
+    }
 
-        if mon.get_task() != &task {
-            mon.reload(task);
+    // release leave the server without stopping the tasks, their outputs being
+    // handed over to the next server
+    pub fn release(&mut self) {
+        log::info!("leaving every task running");
+        let saved = self
+            .monitors
+            .iter_mut()
+            .map(|(name, manager)| (name.clone(), manager.release()))
+            .collect();
+
+        self.save(&saved);
+    }
+
+    // take_back adopt again the children released for a handover that failed,
+    // they are still ours along with their pipes
+    pub fn take_back(&mut self) {
+        let Some(persisted) = &self.persisted else {
+            return;
+        };
+        for (name, saved) in persisted.load() {
+            if let Some(manager) = self.monitors.get_mut(&name) {
+                manager.adopt(&saved, true);
+            }
         }
     }
 
+    pub fn handover_refused(response: &Sender<Com>) {
+        F::send_error(
+            response,
+            String::from("cannot hand the tasks over without a --state-file"),
+        )
+        .unwrap();
+    }
+
+    // reload_diff tell what a reload would change, without touching any process
+    pub fn reload_diff(&self, response: &Sender<Com>, watcher: &Watcher) {
+        match ConfigFile::try_from(watcher) {
+            Ok(configfile) => {
+                let current = self
+                    .monitors
+                    .iter()
+                    .map(|(name, manager)| (name, manager.get_task()));
+                let diff = ReloadDiff::new(current, &configfile.order, &configfile.tasks);
+
+                F::send_reload_diff(response, &diff).unwrap();
+            }
+            Err(e) => Self::reload_failed(response, &e),
+        }
+    }
+
+    pub fn reload_failed(response: &Sender<Com>, err: &error::Taskmaster) {
+        F::send_error(response, format!("unable to reload config: {err}")).unwrap();
+    }
+
+    pub fn is_allowed(&self, peer: Peer, message: &Message) -> bool {
+        access::is_allowed(&self.access, Uid::current(), peer, message)
+    }
+
+    pub fn permission_denied(response: &Sender<Com>, peer: Peer, message: &Message) {
+        log::warn!("denied {message:?} to {peer:?}");
+        F::send_error(response, String::from("permission denied")).unwrap();
+    }
+
+    // may_reload_task return whether the reloaded task must be started again
+    fn may_reload_task(&mut self, name: &str, task: Task) -> bool {
+        let mon = self.monitors.get_mut(name).unwrap();
+
+        mon.get_task() != &task && mon.reload(task)
+    }
+
     fn add_task(&mut self, name: &str, task: Task) {
         let mon = Monitor::new(name.to_string(), task);
-        if mon.is_running() {
-            self.start_waiting_thread_if_needed();
+        self.monitors.insert(name.to_string(), mon);
+    }
+
+    // start_task start the dependencies not started yet, the task waits for them to be ready
+    fn start_task(&mut self, name: &str) {
+        let dependencies = self.monitors[name].get_task().depends_on.clone();
+
+        for dependency in &dependencies {
+            if self.monitors[dependency].is_startable() {
+                self.start_task(dependency);
+            }
+        }
+        let ready = self.dependencies_ready(name);
+        let mon = self.monitors.get_mut(name).unwrap();
+        if ready {
+            mon.start();
+        } else {
+            mon.wait();
         }
-        self.monitors.lock().unwrap().insert(name.to_string(), mon);
     }
 
-    fn unknown_taskid(&self, taskid: &str) {
-        log::error!("task {} doesn't exist", taskid);
-        F::send_error(&self.response, format!("unknown taskid {}", taskid)).unwrap();
+    fn dependencies_ready(&self, name: &str) -> bool {
+        self.monitors[name]
+            .get_task()
+            .depends_on
+            .iter()
+            .all(|dependency| self.monitors[dependency].is_ready())
     }
 
-    pub fn start(&mut self, name: &str) {
-        log::debug!("starting task {}", name);
-        if let Some(mon) = self.monitors.lock().unwrap().get_mut(name) {
-            mon.start();
+    fn unknown_taskid(response: &Sender<Com>, taskid: &str) {
+        log::error!("task {taskid} doesn't exist");
+        F::send_error(response, format!("unknown taskid {taskid}")).unwrap();
+    }
+
+    fn unknown_group(response: &Sender<Com>, group: &str) {
+        log::error!("group {group} doesn't exist");
+        F::send_error(response, format!("unknown group {group}")).unwrap();
+    }
+
+    // target resolve a task id to the task it names, or to the tasks its selector matches
+    fn target(&self, response: &Sender<Com>, id: &str) -> Option<Target> {
+        if self.monitors.contains_key(id) {
+            return Some(Target::Task(id.to_string()));
+        }
+        let tasks: Vec<String> = match Selector::parse(id) {
+            Ok(Selector::Task(name)) => {
+                Self::unknown_taskid(response, &name);
+                return None;
+            }
+            Ok(Selector::Group(group)) => {
+                if let Some(tasks) = self.groups.get(&group) {
+                    tasks.clone()
+                } else {
+                    Self::unknown_group(response, &group);
+                    return None;
+                }
+            }
+            Ok(Selector::Pattern(regex)) => self
+                .order
+                .iter()
+                .filter(|name| regex.is_match(name))
+                .cloned()
+                .collect(),
+            Err(e) => {
+                log::error!("{e}");
+                F::send_error(response, e).unwrap();
+                return None;
+            }
+        };
+
+        if tasks.is_empty() {
+            log::error!("no task matches {id}");
+            F::send_error(response, format!("no task matches {id}")).unwrap();
+            None
         } else {
-            self.unknown_taskid(name);
+            Some(Target::Tasks(id.to_string(), tasks))
         }
-        self.start_waiting_thread_if_needed();
     }
 
-    fn start_waiting_thread_if_needed(&mut self) {
-        if !self.waiter_running.load(Ordering::SeqCst) {
-            self.spawn_waiting_thread();
+    // report send the status of the targeted tasks, once a command is applied to them,
+    // so the client can tell whether it failed
+    fn report(&self, response: &Sender<Com>, target: &Target) {
+        match target {
+            Target::Task(name) => {
+                let status = self.monitors[name].detailed_status();
+
+                F::send_status(response, name, &status).unwrap();
+            }
+            Target::Tasks(selector, tasks) => {
+                let statuses = tasks
+                    .iter()
+                    .map(|name| (name.clone(), self.monitors[name].detailed_status()))
+                    .collect();
+
+                F::send_statuses(response, selector, statuses).unwrap();
+            }
         }
     }
 
-    pub fn info(&mut self, name: &str) {
-        log::debug!("Get info on task {}", name);
-        if let Some(mon) = self.monitors.lock().unwrap().get_mut(name) {
-            F::send_task(&self.response, name, &mon.get_task()).unwrap();
-        } else {
-            self.unknown_taskid(name);
+    pub fn start(&mut self, response: &Sender<Com>, id: &str) {
+        log::debug!("starting {id}");
+        if let Some(target) = self.target(response, id) {
+            for name in target.tasks() {
+                self.start_task(name);
+            }
+            self.report(response, &target);
         }
     }
 
-    pub fn list(&mut self) {
+    pub fn info(&mut self, response: &Sender<Com>, id: &str) {
+        log::debug!("Get info on {id}");
+        if let Some(target) = self.target(response, id) {
+            for name in target.tasks() {
+                F::send_task(response, name, self.monitors[name].get_task()).unwrap();
+            }
+        }
+    }
+
+    pub fn list(&mut self, response: &Sender<Com>) {
         log::debug!("setting list");
         F::send_tasks(
-            &self.response,
+            response,
             &mut self
                 .monitors
-                .lock()
-                .unwrap()
                 .iter()
                 .map(|(k, v)| (k.clone(), v.get_task().clone())),
         )
         .unwrap();
     }
 
-    pub fn status(&self, taskname: &str) {
-        log::debug!("retrieving status of {}", taskname);
-        if let Some(manager) = self.monitors.lock().unwrap().get(taskname) {
-            let status = manager.status();
+    pub fn status(&self, response: &Sender<Com>, id: &str) {
+        log::debug!("retrieving status of {id}");
+        if let Some(target) = self.target(response, id) {
+            self.report(response, &target);
+        }
+    }
 
-            F::send_status(&self.response, taskname, status).unwrap();
-        } else {
-            self.unknown_taskid(taskname);
+    pub fn stop(&mut self, response: &Sender<Com>, id: &str) {
+        if let Some(target) = self.target(response, id) {
+            for name in target.tasks() {
+                self.monitors.get_mut(name).unwrap().stop();
+            }
+            self.report(response, &target);
         }
     }
 
-    pub fn stop(&mut self, taskid: &str) {
-        let mut process_manager = self.monitors.lock().unwrap();
+    pub fn restart(&mut self, response: &Sender<Com>, id: &str) {
+        if let Some(target) = self.target(response, id) {
+            for name in target.tasks() {
+                self.monitors.get_mut(name).unwrap().restart();
+            }
+            self.report(response, &target);
+        }
+    }
 
-        if let Some(manager) = process_manager.get_mut(taskid) {
-            manager.stop();
-        } else {
-            self.unknown_taskid(taskid);
+    pub fn pause(&mut self, response: &Sender<Com>, id: &str) {
+        self.apply(response, id, Monitor::pause);
+    }
+
+    pub fn resume(&mut self, response: &Sender<Com>, id: &str) {
+        self.apply(response, id, Monitor::resume);
+    }
+
+    // apply run a command that may not suit every task, the tasks of a selection
+    // it doesn't suit are left alone
+    fn apply(
+        &mut self,
+        response: &Sender<Com>,
+        id: &str,
+        command: impl Fn(&mut Monitor) -> Result<(), String>,
+    ) {
+        let Some(target) = self.target(response, id) else {
+            return;
+        };
+
+        for name in target.tasks() {
+            if let Err(e) = command(self.monitors.get_mut(name).unwrap()) {
+                log::error!("{e}");
+                if !target.is_selection() {
+                    F::send_error(response, e).unwrap();
+                    return;
+                }
+            }
+        }
+        self.report(response, &target);
+    }
+
+    // signal send a signal to the running children of the tasks, or to one instance
+    pub fn signal(
+        &mut self,
+        response: &Sender<Com>,
+        id: &str,
+        signal: &str,
+        instance: Option<u32>,
+    ) {
+        match nix_utils::parse_signal(signal) {
+            Ok(signal) => self.apply(response, id, |manager| manager.signal(signal, instance)),
+            Err(e) => {
+                log::error!("{e}");
+                F::send_error(response, e.to_string()).unwrap();
+            }
         }
     }
 
-    pub fn restart(&mut self, taskid: &str) {
-        let mut process_manager = self.monitors.lock().unwrap();
+    pub fn tail(&self, response: &Sender<Com>, id: &str, lines: usize) {
+        log::debug!("sending the last {lines} lines of {id}");
+        let Some(target) = self.target(response, id) else {
+            return;
+        };
+        let tagged = target.is_selection();
 
-        if let Some(manager) = process_manager.get_mut(taskid) {
-            manager.restart()
+        for name in target.tasks() {
+            let tail = self.monitors[name].tail();
+            let tail = tail.lock().unwrap();
+            let last = tail.last(lines);
+
+            if tagged {
+                F::send_tagged_lines(response, name, &last, tail.is_prefixed()).unwrap();
+            } else {
+                F::send_lines(response, name, &last, tail.is_prefixed()).unwrap();
+            }
+        }
+    }
+
+    // follow register the client to receive the output of the tasks, until it leaves
+    pub fn follow(&self, response: &Sender<Com>, id: &str) -> bool {
+        log::debug!("following {id}");
+        let Some(target) = self.target(response, id) else {
+            return false;
+        };
+        let tagged = target.is_selection();
+
+        for name in target.tasks() {
+            let tail = self.monitors[name].tail();
+            let mut tail = tail.lock().unwrap();
+            let backlog = tail.last(FOLLOW_BACKLOG);
+
+            // a client already gone is forgotten on the next line
+            if tagged {
+                drop(F::send_tagged_lines(
+                    response,
+                    name,
+                    &backlog,
+                    tail.is_prefixed(),
+                ));
+                tail.follow(response.clone(), F::format_tagged_line);
+            } else {
+                drop(F::send_lines(response, name, &backlog, tail.is_prefixed()));
+                tail.follow(response.clone(), F::format_line);
+            }
+        }
+        true
+    }
+
+    // reap dispatch every terminated child to the monitor owning it
+    pub fn reap(&mut self) {
+        for (pid, status) in reaper::reap() {
+            let owned = self
+                .monitors
+                .values_mut()
+                .any(|manager| manager.child_exited(pid, status));
+
+            if !owned {
+                log::debug!("reaped child-{pid} not owned by any task");
+            }
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<time::Instant> {
+        let now = time::Instant::now();
+        let dependencies = self
+            .monitors
+            .values()
+            .filter(|manager| manager.status() == Status::Waiting)
+            .flat_map(|manager| &manager.get_task().depends_on)
+            .filter_map(|dependency| self.monitors[dependency].ready_at())
+            .filter(|ready_at| *ready_at > now);
+
+        self.monitors
+            .values()
+            .filter_map(Monitor::next_deadline)
+            .chain(dependencies)
+            .min()
+    }
+
+    pub fn cycle(&mut self) {
+        for monitor in self.monitors.values_mut() {
+            monitor.cycle(&self.event);
+        }
+        if self.shutting_down {
+            self.stop_next();
         } else {
-            self.unknown_taskid(taskid);
-        }
-    }
-
-    fn spawn_waiting_thread(&mut self) {
-        let process_manager_mut = self.monitors.clone();
-        let running_state = self.waiter_running.clone();
-        let sender = self.sender.clone();
-
-        running_state.store(true, Ordering::SeqCst);
-        self.thread = Some(thread::spawn(move || {
-            log::debug!("waiter thread spawned !");
-            loop {
-                let mut process_manager = process_manager_mut.lock().unwrap();
-                let working_manager = process_manager
-                    .values_mut()
-                    .filter(|manager| manager.is_running());
-                let mut working_manager_count = 0;
-                let mut finished_manager_count = 0;
-
-                working_manager.for_each(|manager| {
-                    working_manager_count += 1;
-                    manager.cycle(&sender);
-                    if manager.has_finished() {
-                        finished_manager_count += 1;
-                    }
-                });
-                if working_manager_count == finished_manager_count {
-                    break;
-                }
-                drop(process_manager);
-                thread::sleep(time::Duration::from_millis(500));
+            self.start_waiting();
+        }
+    }
+
+    fn start_waiting(&mut self) {
+        for name in &self.order {
+            if self.monitors[name].status() == Status::Waiting && self.dependencies_ready(name) {
+                self.monitors.get_mut(name).unwrap().start();
             }
-            log::debug!("waiter thread finished !");
-            running_state.store(false, Ordering::SeqCst);
-        }));
+        }
+    }
+
+    // shutdown stop the tasks in the reverse order they were started
+    pub fn shutdown(&mut self) {
+        log::info!("stopping every task before leaving");
+        self.shutting_down = true;
+        self.stop_next();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shutting_down && !self.monitors.values().any(Monitor::is_running)
     }
+
+    // stop_next stop the tasks no running task depends on or comes after
+    fn stop_next(&mut self) {
+        let running: Vec<&Monitor> = self
+            .monitors
+            .values()
+            .filter(|manager| manager.is_running())
+            .collect();
+        let to_stop: Vec<String> = self
+            .monitors
+            .iter()
+            .filter(|(_, manager)| !is_stopped(manager.status()))
+            .filter(|(name, manager)| {
+                !running.iter().any(|other| {
+                    other.get_task().depends_on.contains(name)
+                        || other.get_task().priority > manager.get_task().priority
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in to_stop {
+            self.monitors.get_mut(&name).unwrap().stop();
+        }
+    }
+}
+
+fn is_stopped(status: Status) -> bool {
+    matches!(
+        status,
+        Status::Inactive
+            | Status::Stopping
+            | Status::Stopped
+            | Status::Finished
+            | Status::Failed
+            | Status::Fatal
+    )
 }
@@ -0,0 +1,157 @@
+use nix::sys::signal::Signal;
+use serde::{
+    de::{self, Deserializer, SeqAccess, Visitor},
+    Deserialize, Serialize,
+};
+use std::{
+    fmt::{self, Display},
+    time,
+};
+
+use super::{default, nix_utils};
+
+// StopStep is a signal sent to a stopping child, and how long it has to exit
+// before the next step, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopStep {
+    #[serde(with = "nix_utils::SerdeSignal")]
+    pub signal: Signal,
+
+    #[serde(default = "default::stop_delay")]
+    pub delay: u32,
+}
+
+impl Display for StopStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} for {}s", self.signal, self.delay)
+    }
+}
+
+// StopSignal is a single signal followed by stopdelay, or a list of steps,
+// SIGKILL comes after the last one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum StopSignal {
+    Single(#[serde(with = "nix_utils::SerdeSignal")] Signal),
+    Steps(Vec<StopStep>),
+}
+
+impl StopSignal {
+    // steps is the signals to send, with the delay each one is given
+    pub fn steps(&self, stopdelay: u32) -> Vec<(Signal, time::Duration)> {
+        let delay = |secs: u32| time::Duration::from_secs(secs.into());
+
+        match self {
+            StopSignal::Single(signal) => vec![(*signal, delay(stopdelay))],
+            StopSignal::Steps(steps) => steps
+                .iter()
+                .map(|step| (step.signal, delay(step.delay)))
+                .collect(),
+        }
+    }
+}
+
+impl Display for StopSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopSignal::Single(signal) => write!(f, "{signal}"),
+            StopSignal::Steps(steps) => {
+                let steps: Vec<String> = steps.iter().map(ToString::to_string).collect();
+                write!(f, "{}", steps.join(", "))
+            }
+        }
+    }
+}
+
+// an untagged enum would hide why a signal name is refused
+impl<'de> Deserialize<'de> for StopSignal {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct StopSignalVisitor;
+
+        impl<'de> Visitor<'de> for StopSignalVisitor {
+            type Value = StopSignal;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a signal name or a list of {{signal, delay}} steps")
+            }
+
+            fn visit_str<E: de::Error>(self, raw: &str) -> Result<StopSignal, E> {
+                nix_utils::parse_signal(raw)
+                    .map(StopSignal::Single)
+                    .map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<StopSignal, A::Error> {
+                let mut steps = Vec::new();
+
+                while let Some(step) = seq.next_element()? {
+                    steps.push(step);
+                }
+                if steps.is_empty() {
+                    return Err(de::Error::custom("at least one stop step is needed"));
+                }
+                Ok(StopSignal::Steps(steps))
+            }
+        }
+
+        deserializer.deserialize_any(StopSignalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test_stop {
+    use super::{StopSignal, StopStep};
+    use nix::sys::signal::Signal;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_stop_signal() {
+        let single: StopSignal = serde_yaml::from_str("TERM").unwrap();
+        assert_eq!(single, StopSignal::Single(Signal::SIGTERM));
+        assert_eq!(
+            single.steps(5),
+            vec![(Signal::SIGTERM, Duration::from_secs(5))]
+        );
+
+        let steps: StopSignal =
+            serde_yaml::from_str("[{signal: INT, delay: 10}, {signal: SIGQUIT}]").unwrap();
+        assert_eq!(
+            steps,
+            StopSignal::Steps(vec![
+                StopStep {
+                    signal: Signal::SIGINT,
+                    delay: 10
+                },
+                StopStep {
+                    signal: Signal::SIGQUIT,
+                    delay: 2
+                },
+            ])
+        );
+        assert_eq!(steps.to_string(), "SIGINT for 10s, SIGQUIT for 2s");
+        assert_eq!(
+            steps.steps(5),
+            vec![
+                (Signal::SIGINT, Duration::from_secs(10)),
+                (Signal::SIGQUIT, Duration::from_secs(2))
+            ]
+        );
+
+        let steps: StopSignal =
+            toml::from_str::<toml::Value>("s = [{signal = \"TERM\", delay = 3}]").unwrap()["s"]
+                .clone()
+                .try_into()
+                .unwrap();
+        assert_eq!(
+            steps.steps(5),
+            vec![(Signal::SIGTERM, Duration::from_secs(3))]
+        );
+
+        let invalid = serde_yaml::from_str::<StopSignal>("NOPE").unwrap_err();
+        assert!(invalid.to_string().contains("invalid signal NOPE"));
+        assert!(serde_yaml::from_str::<StopSignal>("[]").is_err());
+    }
+}
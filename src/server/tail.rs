@@ -0,0 +1,220 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{mpsc::Sender, Arc, Mutex},
+};
+
+use super::communication::Com;
+
+// Number of lines kept for each child
+pub const CAPACITY: usize = 1000;
+
+// Longer lines are split, so a child never writing a newline can't eat the memory
+const MAX_LINE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogLine {
+    pub instance: u32,
+    pub stream: Stream,
+    pub line: String,
+}
+
+// format turn a line into what is sent to the client
+pub type LineFormat = fn(&str, &LogLine, bool) -> String;
+
+struct Follower {
+    sender: Sender<Com>,
+    format: LineFormat,
+}
+
+#[derive(Default)]
+pub struct Tail {
+    taskid: String,
+    prefixed: bool,
+    seq: u64,
+    children: BTreeMap<u32, VecDeque<(u64, LogLine)>>,
+    followers: Vec<Follower>,
+}
+
+pub type SharedTail = Arc<Mutex<Tail>>;
+
+impl Tail {
+    pub fn new(taskid: String, prefixed: bool) -> Self {
+        Self {
+            taskid,
+            prefixed,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_prefixed(&self) -> bool {
+        self.prefixed
+    }
+
+    pub fn set_prefixed(&mut self, prefixed: bool) {
+        self.prefixed = prefixed;
+    }
+
+    pub fn push(&mut self, line: LogLine) {
+        let (taskid, prefixed) = (&self.taskid, self.prefixed);
+        self.followers.retain(|follower| {
+            let formatted = (follower.format)(taskid, &line, prefixed);
+            follower.sender.send(Com::Msg(formatted)).is_ok()
+        });
+
+        let buffer = self.children.entry(line.instance).or_default();
+        if buffer.len() == CAPACITY {
+            buffer.pop_front();
+        }
+        buffer.push_back((self.seq, line));
+        self.seq += 1;
+    }
+
+    // last return the n most recent lines of every child, oldest first
+    pub fn last(&self, n: usize) -> Vec<LogLine> {
+        let mut lines: Vec<&(u64, LogLine)> = self.children.values().flatten().collect();
+
+        lines.sort_unstable_by_key(|(seq, _)| *seq);
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .map(|(_, line)| line.clone())
+            .collect()
+    }
+
+    pub fn follow(&mut self, sender: Sender<Com>, format: LineFormat) {
+        self.followers.push(Follower { sender, format });
+    }
+
+    // prune forget the oldest finished children, keeping the running ones
+    pub fn prune(&mut self, running: &[u32], keep: usize) {
+        let finished: Vec<u32> = self
+            .children
+            .keys()
+            .filter(|instance| !running.contains(instance))
+            .copied()
+            .collect();
+
+        for instance in finished.iter().take(finished.len().saturating_sub(keep)) {
+            self.children.remove(instance);
+        }
+    }
+}
+
+// Lines accumulate the bytes read from a pipe until a newline is found
+#[derive(Debug, Default)]
+pub struct Lines {
+    pending: Vec<u8>,
+}
+
+impl Lines {
+    pub fn feed(&mut self, buf: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for &c in buf {
+            if c == b'\n' {
+                lines.push(self.take());
+            } else {
+                self.pending.push(c);
+                if self.pending.len() == MAX_LINE {
+                    lines.push(self.take());
+                }
+            }
+        }
+        lines
+    }
+
+    // flush return what is left once the pipe is closed
+    pub fn flush(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(self.take())
+        }
+    }
+
+    fn take(&mut self) -> String {
+        let line = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        line
+    }
+}
+
+#[cfg(test)]
+mod test_tail {
+    use super::{Lines, LogLine, Stream, Tail, CAPACITY};
+    use std::sync::mpsc::channel;
+
+    fn line(instance: u32, text: &str) -> LogLine {
+        LogLine {
+            instance,
+            stream: Stream::Stdout,
+            line: String::from(text),
+        }
+    }
+
+    fn format(_taskid: &str, line: &LogLine, _prefixed: bool) -> String {
+        line.line.clone()
+    }
+
+    #[test]
+    fn test_last() {
+        let mut tail = Tail::new(String::from("web"), true);
+
+        tail.push(line(0, "a"));
+        tail.push(line(1, "b"));
+        tail.push(line(0, "c"));
+        assert_eq!(tail.last(2), vec![line(1, "b"), line(0, "c")]);
+        assert_eq!(tail.last(10).len(), 3);
+
+        for _ in 0..CAPACITY {
+            tail.push(line(0, "x"));
+        }
+        assert_eq!(tail.last(usize::MAX).len(), CAPACITY + 1);
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut tail = Tail::new(String::from("web"), true);
+
+        for instance in 0..5 {
+            tail.push(line(instance, "x"));
+        }
+        tail.prune(&[0], 2);
+        let instances: Vec<u32> = tail.last(10).iter().map(|l| l.instance).collect();
+        assert_eq!(instances, vec![0, 3, 4]);
+    }
+
+    #[test]
+    fn test_follow() {
+        let mut tail = Tail::new(String::from("web"), false);
+        let (sender, receiver) = channel();
+
+        tail.follow(sender, format);
+        tail.push(line(0, "a"));
+        assert_eq!(tail.followers.len(), 1);
+        drop(receiver);
+        tail.push(line(0, "b"));
+        assert!(tail.followers.is_empty());
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut lines = Lines::default();
+
+        assert_eq!(lines.feed(b"foo\nba"), vec![String::from("foo")]);
+        assert_eq!(
+            lines.feed(b"r\n\nbaz"),
+            vec![String::from("bar"), String::new()]
+        );
+        assert_eq!(lines.flush(), Some(String::from("baz")));
+        assert_eq!(lines.flush(), None);
+    }
+}
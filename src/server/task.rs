@@ -1,24 +1,49 @@
-use super::{default, error, nix_utils, relaunch::Relaunch, watcher::Watcher};
+use super::{
+    access::AccessRule,
+    backoff::Backoff,
+    check, default, dependency, error,
+    health::HealthCheck,
+    nix_utils,
+    output::{OutputConfig, OutputMode},
+    relaunch::Relaunch,
+    schedule::{Schedule, When},
+    stop::StopSignal,
+    watcher::Watcher,
+};
 use nix::{
-    sys::{
-        signal::Signal,
-        stat::{self, Mode},
-    },
-    unistd::{Gid, Uid},
+    sys::stat::{self, Mode},
+    unistd::{Gid, Group, Uid, User},
 };
-use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{self, de::Error, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     collections::BTreeMap,
     convert::TryFrom,
-    fmt,
-    fs::{self, File},
+    ffi::OsStr,
+    fmt::{self, Display},
+    fs,
     os::unix::process::CommandExt,
-    path::PathBuf,
-    process::{Child, Command},
+    path::{Path, PathBuf},
+    process::Command,
     time,
 };
 
-pub type ConfigFile = BTreeMap<String, Task>;
+#[derive(Debug, Deserialize)]
+pub struct ConfigFile {
+    // Rules granting commands to peers of the control socket
+    #[serde(default)]
+    pub access: Vec<AccessRule>,
+
+    // Tasks addressed together as group:<name>
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+
+    #[serde(flatten)]
+    pub tasks: BTreeMap<String, Task>,
+
+    // Task names, each one after its dependencies
+    #[serde(skip)]
+    pub order: Vec<String>,
+}
 
 impl TryFrom<&Watcher> for ConfigFile {
     type Error = error::Taskmaster;
@@ -30,36 +55,82 @@ impl TryFrom<&Watcher> for ConfigFile {
         };
         let ext = watcher.path.extension().and_then(std::ffi::OsStr::to_str);
 
-        match ext {
-            Some("yml") | Some("yaml") => {
+        let mut config: ConfigFile = match ext {
+            Some("yml" | "yaml") => {
                 log::info!("try parsing in YAML format");
                 match serde_yaml::from_str(&content) {
-                    Ok(c) => Ok(c),
-                    Err(e) => Err(error::Taskmaster::ParseYaml(e)),
+                    Ok(c) => c,
+                    Err(e) => return Err(error::Taskmaster::ParseYaml(e)),
                 }
             }
             Some("toml") => {
                 log::info!("try parsing in TOML format");
                 match toml::from_str(&content) {
-                    Ok(c) => Ok(c),
-                    Err(e) => Err(error::Taskmaster::ParseToml(e)),
+                    Ok(c) => c,
+                    Err(e) => return Err(error::Taskmaster::ParseToml(e)),
                 }
             }
             Some(ext) => {
-                log::error!("no handler for extension '{}'", ext);
-                Err(error::Taskmaster::Cli)
+                log::error!("no handler for extension '{ext}'");
+                return Err(error::Taskmaster::Cli);
             }
             None => {
                 log::error!("cannot determine file type by extension");
-                Err(error::Taskmaster::Cli)
+                return Err(error::Taskmaster::Cli);
+            }
+        };
+        config.order = dependency::order(&config.tasks)
+            .map_err(|e| error::Taskmaster::Dependency(e.to_string()))?;
+        config.check_groups().map_err(error::Taskmaster::Group)?;
+        Ok(config)
+    }
+}
+
+impl ConfigFile {
+    fn check_groups(&self) -> Result<(), String> {
+        for (group, tasks) in &self.groups {
+            if let Some(task) = tasks.iter().find(|task| !self.tasks.contains_key(*task)) {
+                return Err(format!("group {group} contains unknown task {task}"));
             }
         }
+        Ok(())
+    }
+}
+
+const SHELL: &str = "/bin/sh";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+    Missing,
+    Conflicting,
+    Empty,
+    ShellWithArgs,
+    Parse(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Missing => write!(f, "one of `cmd` or `args` is required"),
+            CommandError::Conflicting => write!(f, "`cmd` and `args` are mutually exclusive"),
+            CommandError::Empty => write!(f, "empty command"),
+            CommandError::ShellWithArgs => write!(f, "`shell` mode requires `cmd`"),
+            CommandError::Parse(cmd) => write!(f, "cannot parse command {cmd:?}"),
+        }
     }
 }
 
 #[derive(Deserialize, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
 struct TaskPartial {
-    pub cmd: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+
+    #[serde(default = "default::shell")]
+    pub shell: bool,
 
     #[serde(default = "default::autostart")]
     pub autostart: bool,
@@ -73,18 +144,45 @@ struct TaskPartial {
     #[serde(default = "default::workdir")]
     pub workingdir: PathBuf,
 
-    #[serde(with = "nix_utils::SerdeSignal", default = "default::stop_signal")]
-    pub stopsignal: Signal,
+    #[serde(default = "default::stop_signal")]
+    pub stopsignal: StopSignal,
 
     #[serde(default = "default::stop_delay")]
     pub stopdelay: u32,
 
+    #[serde(default)]
+    pub timeout: u32,
+
+    #[serde(default = "default::as_group")]
+    pub stopasgroup: bool,
+
+    #[serde(default = "default::as_group")]
+    pub killasgroup: bool,
+
     #[serde(default = "default::stdout")]
     pub stdout: String,
 
     #[serde(default = "default::stderr")]
     pub stderr: String,
 
+    #[serde(default)]
+    pub stdout_maxbytes: u64,
+
+    #[serde(default = "default::backups")]
+    pub stdout_backups: u32,
+
+    #[serde(default)]
+    pub stderr_maxbytes: u64,
+
+    #[serde(default = "default::backups")]
+    pub stderr_backups: u32,
+
+    #[serde(default = "default::output_mode")]
+    pub output_mode: OutputMode,
+
+    #[serde(default)]
+    pub output_gzip: bool,
+
     #[serde(default = "default::retry")]
     pub retry: u32,
 
@@ -97,6 +195,30 @@ struct TaskPartial {
     #[serde(default = "default::relaunch_mode")]
     pub restart: Relaunch,
 
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    #[serde(default = "default::priority")]
+    pub priority: u32,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<HealthCheck>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+
+    #[serde(default)]
+    pub backoff_initial: u32,
+
+    #[serde(default = "default::backoff_max")]
+    pub backoff_max: u32,
+
+    #[serde(default = "default::backoff_multiplier")]
+    pub backoff_multiplier: u32,
+
+    #[serde(default = "default::backoff_jitter")]
+    pub backoff_jitter: u32,
+
     #[serde(default = "default::env")]
     pub env: BTreeMap<String, String>,
 
@@ -110,18 +232,37 @@ impl From<Task> for TaskPartial {
     fn from(task: Task) -> TaskPartial {
         TaskPartial {
             cmd: task.cmd,
+            args: task.args,
+            shell: task.shell,
             autostart: task.autostart,
             numprocess: task.numprocess,
             umask: task.umask,
             workingdir: task.workingdir,
             stopsignal: task.stopsignal,
             stopdelay: task.stopdelay,
+            timeout: task.timeout,
+            stopasgroup: task.stopasgroup,
+            killasgroup: task.killasgroup,
             stdout: task.stdout,
             stderr: task.stderr,
+            stdout_maxbytes: task.stdout_maxbytes,
+            stdout_backups: task.stdout_backups,
+            stderr_maxbytes: task.stderr_maxbytes,
+            stderr_backups: task.stderr_backups,
+            output_mode: task.output_mode,
+            output_gzip: task.output_gzip,
             retry: task.retry,
             successdelay: task.successdelay,
             exitcodes: task.exitcodes,
             restart: task.restart,
+            depends_on: task.depends_on,
+            priority: task.priority,
+            healthcheck: task.healthcheck,
+            schedule: task.schedule,
+            backoff_initial: task.backoff.initial,
+            backoff_max: task.backoff.max,
+            backoff_multiplier: task.backoff.multiplier,
+            backoff_jitter: task.backoff.jitter,
             env: task.env,
             gid: task.gid,
             uid: task.uid,
@@ -130,21 +271,38 @@ impl From<Task> for TaskPartial {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Task {
-    cmd: String,
-    args: Vec<String>,
+    cmd: Option<String>,
+    args: Option<Vec<String>>,
+    shell: bool,
+    argv: Vec<String>,
     pub autostart: bool,
     pub numprocess: u32,
     umask: Mode,
     workingdir: PathBuf,
-    pub stopsignal: Signal,
+    pub stopsignal: StopSignal,
     pub stopdelay: u32,
+    pub timeout: u32,
+    pub stopasgroup: bool,
+    pub killasgroup: bool,
     stdout: String,
     stderr: String,
+    stdout_maxbytes: u64,
+    stdout_backups: u32,
+    stderr_maxbytes: u64,
+    stderr_backups: u32,
+    output_mode: OutputMode,
+    output_gzip: bool,
     pub retry: u32,
     pub successdelay: u32,
     pub exitcodes: Vec<i32>,
     pub restart: Relaunch,
+    pub depends_on: Vec<String>,
+    pub priority: u32,
+    pub healthcheck: Option<HealthCheck>,
+    pub schedule: Option<Schedule>,
+    pub backoff: Backoff,
     env: BTreeMap<String, String>,
     uid: Option<Uid>,
     gid: Option<Gid>,
@@ -156,7 +314,7 @@ impl<'de> Deserialize<'de> for Task {
         D: Deserializer<'de>,
     {
         let partial = TaskPartial::deserialize(deserializer)?;
-        Ok(Task::from(partial))
+        Task::try_from(partial).map_err(D::Error::custom)
     }
 }
 
@@ -170,31 +328,81 @@ impl Serialize for Task {
     }
 }
 
-impl From<TaskPartial> for Task {
-    fn from(partial: TaskPartial) -> Self {
-        Self {
-            cmd: partial.cmd.clone(),
-            args: partial
-                .cmd
-                .split(' ')
-                .map(std::string::ToString::to_string)
-                .collect(),
+impl TryFrom<TaskPartial> for Task {
+    type Error = CommandError;
+
+    fn try_from(partial: TaskPartial) -> Result<Self, CommandError> {
+        let argv = build_argv(
+            partial.cmd.as_deref(),
+            partial.args.as_deref(),
+            partial.shell,
+        )?;
+
+        Ok(Self {
+            cmd: partial.cmd,
+            args: partial.args,
+            shell: partial.shell,
+            argv,
             autostart: partial.autostart,
             numprocess: partial.numprocess,
             umask: partial.umask,
             workingdir: partial.workingdir,
             stopsignal: partial.stopsignal,
             stopdelay: partial.stopdelay,
+            timeout: partial.timeout,
+            stopasgroup: partial.stopasgroup,
+            killasgroup: partial.killasgroup,
             stdout: partial.stdout,
             stderr: partial.stderr,
+            stdout_maxbytes: partial.stdout_maxbytes,
+            stdout_backups: partial.stdout_backups,
+            stderr_maxbytes: partial.stderr_maxbytes,
+            stderr_backups: partial.stderr_backups,
+            output_mode: partial.output_mode,
+            output_gzip: partial.output_gzip,
             retry: partial.retry,
             successdelay: partial.successdelay,
             exitcodes: partial.exitcodes,
             restart: partial.restart,
+            depends_on: partial.depends_on,
+            priority: partial.priority,
+            healthcheck: partial.healthcheck,
+            schedule: partial.schedule,
+            backoff: Backoff {
+                initial: partial.backoff_initial,
+                max: partial.backoff_max,
+                multiplier: partial.backoff_multiplier,
+                jitter: partial.backoff_jitter,
+            },
             env: partial.env,
             gid: partial.gid,
             uid: partial.uid,
+        })
+    }
+}
+
+fn build_argv(
+    cmd: Option<&str>,
+    args: Option<&[String]>,
+    shell: bool,
+) -> Result<Vec<String>, CommandError> {
+    let words = match (cmd, args) {
+        (Some(cmd), None) if shell => {
+            vec![String::from(SHELL), String::from("-c"), cmd.to_string()]
+        }
+        (Some(cmd), None) => {
+            shell_words::split(cmd).map_err(|_| CommandError::Parse(cmd.to_string()))?
         }
+        (None, Some(_)) if shell => return Err(CommandError::ShellWithArgs),
+        (None, Some(args)) => args.to_vec(),
+        (Some(_), Some(_)) => return Err(CommandError::Conflicting),
+        (None, None) => return Err(CommandError::Missing),
+    };
+
+    if words.first().is_none_or(String::is_empty) {
+        Err(CommandError::Empty)
+    } else {
+        Ok(words)
     }
 }
 
@@ -202,18 +410,34 @@ impl fmt::Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Command: {}\nNumber of processes: {}\nAutostart: {}\nUmask: {:#05o}\nWorking Directory: {:?}\nStdout: {:?}\nStderr: {:?}\nStop signal: {}\nStop delay: {}\nretry: {}\nSuccess Delay: {}\nExit Codes: {:?}\nRestart: {}\nEnv: {:?}\nPermission: uid: {:?}, gid: {:?}",
-            self.cmd,
+            "Command: {}\nShell: {}\nNumber of processes: {}\nAutostart: {}\nUmask: {:#05o}\nWorking Directory: {}\nStdout: {:?}\nStderr: {:?}\nStdout rotation: {} bytes, {} backups\nStderr rotation: {} bytes, {} backups\nOutput mode: {}\nOutput gzip: {}\nStop signal: {}\nStop delay: {}\nTimeout: {}\nStop as group: {}\nKill as group: {}\nretry: {}\nSuccess Delay: {}\nExit Codes: {:?}\nRestart: {}\nDepends on: {:?}\nPriority: {}\nHealth check: {}\nSchedule: {}\nBackoff: {}s to {}s, x{}, {}% jitter\nEnv: {:?}\nPermission: uid: {:?}, gid: {:?}",
+            self.cmd
+                .clone()
+                .unwrap_or_else(|| shell_words::join(&self.argv)),
+            self.shell,
             self.numprocess,
             self.autostart,
             self.umask,
-            self.workingdir,
+            self.workingdir.display(),
 
             self.stdout,
             self.stderr,
+            self.stdout_maxbytes,
+            self.stdout_backups,
+            self.stderr_maxbytes,
+            self.stderr_backups,
+            self.output_mode,
+            self.output_gzip,
 
             self.stopsignal,
             self.stopdelay,
+            if self.timeout == 0 {
+                String::from("none")
+            } else {
+                format!("{}s", self.timeout)
+            },
+            self.stopasgroup,
+            self.killasgroup,
 
             self.retry,
 
@@ -222,6 +446,18 @@ impl fmt::Display for Task {
             self.exitcodes,
 
             self.restart,
+            self.depends_on,
+            self.priority,
+            self.healthcheck
+                .as_ref()
+                .map_or_else(|| String::from("none"), ToString::to_string),
+            self.schedule
+                .as_ref()
+                .map_or_else(|| String::from("none"), ToString::to_string),
+            self.backoff.initial,
+            self.backoff.max,
+            self.backoff.multiplier,
+            self.backoff.jitter,
 
             self.env,
 
@@ -232,33 +468,100 @@ impl fmt::Display for Task {
 }
 
 impl Task {
-    pub fn run(&self) -> Vec<Child> {
-        let timestamp = get_current_timestamp();
-        let mut jobs = Vec::new();
-        for id in 0..self.numprocess {
-            let mut command = self.get_command(id, timestamp);
-            jobs.push(command.spawn().expect("Couldn't run command!"));
+    pub fn get_command(&self) -> Command {
+        let mut command = Command::new(&self.argv[0]);
+        self.setup_command(&mut command);
+        command.args(&self.argv[1..]);
+        command.current_dir(self.workingdir.as_path());
+        command
+    }
+
+    pub fn stdout_config(&self, id: u32, timestamp: time::Duration) -> OutputConfig {
+        OutputConfig {
+            path: PathBuf::from(format_filename(&self.stdout, id, timestamp)),
+            maxbytes: self.stdout_maxbytes,
+            backups: self.stdout_backups,
+            mode: self.output_mode,
+            gzip: self.output_gzip,
         }
-        jobs
     }
 
-    pub fn get_command(&self, id: u32, timestamp: time::Duration) -> Command {
-        let mut command = Command::new(&self.args[0]);
-        let stdout_filename = format_filename(&self.stdout, id, timestamp);
-        let stderr_filename = format_filename(&self.stderr, id, timestamp);
-        let stdout = File::create(stdout_filename).unwrap();
-        let stderr = File::create(stderr_filename).unwrap();
-        self.setup_command(&mut command);
-        if self.args.len() > 1 {
-            command.args(&self.args[1..]);
+    pub fn stderr_config(&self, id: u32, timestamp: time::Duration) -> OutputConfig {
+        OutputConfig {
+            path: PathBuf::from(format_filename(&self.stderr, id, timestamp)),
+            maxbytes: self.stderr_maxbytes,
+            backups: self.stderr_backups,
+            mode: self.output_mode,
+            gzip: self.output_gzip,
         }
-        command.current_dir(self.workingdir.as_path());
-        command.stdout(stdout);
-        command.stderr(stderr);
-        command
     }
 
+    // check list what would keep the task from starting, beyond its syntax
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let path = self.env.get("PATH").map(OsStr::new);
+
+        match check::find_program(&self.argv[0], &self.workingdir, path) {
+            Some(program) if check::is_executable(&program) => {}
+            Some(program) => problems.push(format!(
+                "command {} is not an executable file",
+                program.display()
+            )),
+            None => problems.push(format!("command {} not found in PATH", self.argv[0])),
+        }
+        if !self.workingdir.is_dir() {
+            problems.push(format!(
+                "workingdir {} doesn't exist",
+                self.workingdir.display()
+            ));
+        }
+        if let Some(uid) = self.uid {
+            if !matches!(User::from_uid(uid), Ok(Some(_))) {
+                problems.push(format!("uid {uid} doesn't exist"));
+            }
+        }
+        if let Some(gid) = self.gid {
+            if !matches!(Group::from_gid(gid), Ok(Some(_))) {
+                problems.push(format!("gid {gid} doesn't exist"));
+            }
+        }
+        for (name, format) in &[("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            let file = format_filename(format, 0, get_current_timestamp());
+            if file != default::stdout() {
+                if let Err(e) = check::is_writable(Path::new(&file)) {
+                    problems.push(format!("{name}: {e}"));
+                }
+            }
+        }
+        for code in self
+            .exitcodes
+            .iter()
+            .filter(|code| !(0..=255).contains(*code))
+        {
+            problems.push(format!("exit code {code} is out of 0..=255"));
+        }
+        if self.numprocess == 0 {
+            problems.push(String::from("numprocess must be greater than 0"));
+        }
+        match &self.schedule {
+            Some(schedule) if schedule.when == When::Every(0) => {
+                problems.push(String::from("schedule every must be greater than 0"));
+            }
+            Some(schedule)
+                if schedule
+                    .next_after(get_current_timestamp().as_secs())
+                    .is_none() =>
+            {
+                problems.push(format!("schedule {} never runs", schedule.when));
+            }
+            _ => {}
+        }
+        problems
+    }
+
+    // every child leads its own process group, so its descendants can be signaled with it
     fn setup_command(&self, command: &mut Command) {
+        command.process_group(0);
         self.setup_command_uid_gid(command);
         self.setup_command_umask(command);
         self.setup_command_env(command);
@@ -290,12 +593,6 @@ impl Task {
             command.env(key, value);
         }
     }
-
-    pub fn check_exit_status(&self, status: std::process::ExitStatus) -> bool {
-        status.code().map_or(false, |exitcode| {
-            self.exitcodes.iter().any(|&code| code != exitcode)
-        })
-    }
 }
 
 pub fn get_current_timestamp() -> time::Duration {
@@ -311,10 +608,130 @@ fn format_filename(format: &str, id: u32, timestamp: time::Duration) -> String {
 
 #[cfg(test)]
 mod test_task {
-    use super::{format_filename, get_current_timestamp};
+    use super::{build_argv, format_filename, get_current_timestamp, CommandError, ConfigFile};
     use std::time;
 
     #[test]
+    fn test_build_argv_cmd() {
+        assert_eq!(
+            build_argv(Some("sh -c \"sleep 10; false\""), None, false),
+            Ok(vec![
+                String::from("sh"),
+                String::from("-c"),
+                String::from("sleep 10; false")
+            ])
+        );
+        assert_eq!(
+            build_argv(Some("  sleep   10 "), None, false),
+            Ok(vec![String::from("sleep"), String::from("10")])
+        );
+        assert_eq!(
+            build_argv(Some("echo 'foo"), None, false),
+            Err(CommandError::Parse(String::from("echo 'foo")))
+        );
+        assert_eq!(
+            build_argv(Some("   "), None, false),
+            Err(CommandError::Empty)
+        );
+    }
+
+    #[test]
+    fn test_build_argv_shell() {
+        assert_eq!(
+            build_argv(Some("sleep 1 && echo $HOME"), None, true),
+            Ok(vec![
+                String::from("/bin/sh"),
+                String::from("-c"),
+                String::from("sleep 1 && echo $HOME")
+            ])
+        );
+        let args = vec![String::from("echo")];
+        assert_eq!(
+            build_argv(None, Some(&args), true),
+            Err(CommandError::ShellWithArgs)
+        );
+    }
+
+    #[test]
+    fn test_build_argv_args() {
+        let args = vec![String::from("echo"), String::from("two  spaces")];
+        assert_eq!(build_argv(None, Some(&args), false), Ok(args.clone()));
+        assert_eq!(
+            build_argv(Some("echo"), Some(&args), false),
+            Err(CommandError::Conflicting)
+        );
+        assert_eq!(build_argv(None, None, false), Err(CommandError::Missing));
+        assert_eq!(build_argv(None, Some(&[]), false), Err(CommandError::Empty));
+    }
+
+    #[test]
+    fn test_parse_access_rules() {
+        let config: ConfigFile = serde_yaml::from_str(
+            "access:\n  - uid: 1001\n    allow: [status, list]\nfoo:\n  cmd: echo bar\n",
+        )
+        .unwrap();
+        assert_eq!(config.access.len(), 1);
+        assert_eq!(config.tasks.len(), 1);
+        assert!(config.tasks.contains_key("foo"));
+
+        let config: ConfigFile = toml::from_str("[foo]\ncmd = \"echo bar\"\n").unwrap();
+        assert!(config.access.is_empty());
+        assert!(config.tasks.contains_key("foo"));
+    }
+
+    #[test]
+    fn test_parse_groups() {
+        let config: ConfigFile =
+            serde_yaml::from_str("groups:\n  web: [foo]\nfoo:\n  cmd: echo bar\n").unwrap();
+        assert_eq!(config.groups["web"], vec![String::from("foo")]);
+        assert!(!config.tasks.contains_key("groups"));
+        assert!(config.check_groups().is_ok());
+
+        let config: ConfigFile =
+            serde_yaml::from_str("groups:\n  web: [foo, bar]\nfoo:\n  cmd: echo bar\n").unwrap();
+        assert_eq!(
+            config.check_groups(),
+            Err(String::from("group web contains unknown task bar"))
+        );
+    }
+
+    #[test]
+    fn test_parse_as_group() {
+        let config: ConfigFile = serde_yaml::from_str(
+            "foo:\n  cmd: echo bar\nbar:\n  cmd: echo foo\n  stopasgroup: true\n",
+        )
+        .unwrap();
+        assert!(!config.tasks["foo"].stopasgroup && !config.tasks["foo"].killasgroup);
+        assert!(config.tasks["bar"].stopasgroup && !config.tasks["bar"].killasgroup);
+    }
+
+    #[test]
+    fn test_check() {
+        let config: ConfigFile = serde_yaml::from_str(
+            "ok:\n  cmd: sh -c true\nbad:\n  cmd: /nowhere/run\n  workingdir: /nowhere\n  numprocess: 0\n  exitcodes: [0, 256]\n  stdout: /nowhere/out.log\n",
+        )
+        .unwrap();
+        assert_eq!(config.tasks["ok"].check(), Vec::<String>::new());
+        assert_eq!(
+            config.tasks["bad"].check(),
+            vec![
+                String::from("command /nowhere/run is not an executable file"),
+                String::from("workingdir /nowhere doesn't exist"),
+                String::from("stdout: directory /nowhere doesn't exist"),
+                String::from("exit code 256 is out of 0..=255"),
+                String::from("numprocess must be greater than 0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_at_load() {
+        let res: Result<ConfigFile, _> = serde_yaml::from_str("foo:\n  cmd: echo \"bar\n");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    #[allow(clippy::duration_suboptimal_units)]
     fn test_get_current_timestamp() {
         let timestamp = get_current_timestamp();
         assert!(timestamp > time::Duration::from_secs(0));
@@ -1,22 +1,41 @@
-use std::convert::TryFrom;
-use std::path::PathBuf;
-use std::sync::mpsc::Sender;
-use std::thread;
-use std::time::{Duration, Instant, SystemTime};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor},
+};
+use std::{
+    convert::TryFrom,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    thread,
+    time::Duration,
+};
 
 use super::error;
 use super::inter::Inter;
 
-#[derive(Clone)]
-struct PathData {
-    mtime: SystemTime,
-    last_check: Option<Instant>,
+// WatchConfig tell whether an edit of the config file reloads it, once no
+// other edit came for the debounce delay
+#[derive(Debug, Clone, Copy)]
+pub struct WatchConfig {
+    pub auto_reload: bool,
+    pub debounce: Duration,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            auto_reload: true,
+            debounce: Duration::from_millis(500),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Watcher {
     pub path: PathBuf,
-    data: PathData,
+    config: WatchConfig,
 }
 
 impl TryFrom<&str> for Watcher {
@@ -28,10 +47,7 @@ impl TryFrom<&str> for Watcher {
         if path.exists() {
             let watcher = Self {
                 path,
-                data: PathData {
-                    mtime: SystemTime::now(),
-                    last_check: None,
-                },
+                config: WatchConfig::default(),
             };
             Ok(watcher)
         } else {
@@ -40,35 +56,157 @@ impl TryFrom<&str> for Watcher {
     }
 }
 
+// the directory sees the editors saving through a rename, the file is
+// watched too when it is a link to another directory
+const DIR_EVENTS: AddWatchFlags = AddWatchFlags::from_bits_truncate(
+    AddWatchFlags::IN_CLOSE_WRITE.bits()
+        | AddWatchFlags::IN_MOVED_TO.bits()
+        | AddWatchFlags::IN_CREATE.bits(),
+);
+const FILE_EVENTS: AddWatchFlags = AddWatchFlags::from_bits_truncate(
+    AddWatchFlags::IN_CLOSE_WRITE.bits() | AddWatchFlags::IN_MODIFY.bits(),
+);
+
+// Inotify watches the config file and its directory
+struct Inotified {
+    inotify: Inotify,
+    name: Option<PathBuf>,
+    dir: WatchDescriptor,
+    file: Option<WatchDescriptor>,
+}
+
+impl Inotified {
+    fn new(path: &Path) -> nix::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC)?;
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+
+        Ok(Self {
+            inotify,
+            name: path.file_name().map(PathBuf::from),
+            dir: inotify.add_watch(dir, DIR_EVENTS)?,
+            file: inotify.add_watch(path, FILE_EVENTS).ok(),
+        })
+    }
+
+    // rewatch follow the file replaced by a rename
+    fn rewatch(&mut self, path: &Path) {
+        self.file = self.inotify.add_watch(path, FILE_EVENTS).ok();
+    }
+
+    fn is_config(&self, event: &InotifyEvent) -> bool {
+        if Some(event.wd) == self.file {
+            return true;
+        }
+        event.wd == self.dir && event.name.as_deref().map(Path::new) == self.name.as_deref()
+    }
+
+    // wait for an edit of the config file, until the timeout if any
+    fn wait(&self, timeout: Option<Duration>) -> nix::Result<bool> {
+        let timeout = timeout.map_or(-1, |timeout| {
+            i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+        });
+        let mut fds = [PollFd::new(self.inotify.as_raw_fd(), PollFlags::POLLIN)];
+
+        loop {
+            match poll(&mut fds, timeout) {
+                Ok(0) => return Ok(false),
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+            if self
+                .inotify
+                .read_events()?
+                .iter()
+                .any(|event| self.is_config(event))
+            {
+                return Ok(true);
+            }
+        }
+    }
+}
+
 impl Watcher {
+    pub fn configure(mut self, config: WatchConfig) -> Self {
+        self.config = config;
+        self
+    }
+
     pub fn run(&mut self, sender: Sender<Inter>) {
+        if !self.config.auto_reload {
+            log::info!("auto reload disabled, reload with the client or SIGHUP");
+            return;
+        }
         let path = self.path.clone();
-        let mut data = self.data.clone();
+        let debounce = self.config.debounce;
+        let mut inotified = match Inotified::new(&path) {
+            Ok(inotified) => inotified,
+            Err(e) => {
+                log::error!("cannot watch {}: {}", path.display(), e);
+                return;
+            }
+        };
+
         thread::spawn(move || loop {
-            let delay: Duration = Duration::from_secs(10);
-            if path.is_file() {
-                match path.metadata() {
-                    Err(_) => {
-                        log::error!("can't Access metadata");
-                    }
-                    Ok(metadata) => {
-                        let mtime = metadata.modified().unwrap();
-                        if mtime == data.mtime {
-                            log::debug!("Nothing to be done");
-                        } else {
-                            log::info!("ask to reload config");
-                            data.mtime = mtime;
-                            sender.send(Inter::Reload).unwrap();
-                        }
-                    }
+            match inotified.wait(None) {
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("cannot watch {}: {}", path.display(), e);
+                    return;
                 }
-            } else {
-                log::error!(
-                    "{} is not a file / not exist",
-                    path.clone().into_os_string().into_string().unwrap()
-                );
             }
-            thread::sleep(delay);
+            // an editor may write several times, the last one is reloaded
+            while let Ok(true) = inotified.wait(Some(debounce)) {}
+            inotified.rewatch(&path);
+            log::info!("ask to reload config");
+            if sender.send(Inter::Reload).is_err() {
+                return;
+            }
         });
     }
 }
+
+#[cfg(test)]
+mod test_watcher {
+    use super::{WatchConfig, Watcher};
+    use crate::server::inter::Inter;
+    use std::{convert::TryFrom, env, fs, process, sync::mpsc::channel, thread, time::Duration};
+
+    #[test]
+    fn test_reload_on_rename() {
+        let dir = env::temp_dir().join(format!("taskmaster-watch-{}", process::id()));
+        let path = dir.join("config.yml");
+        let config = WatchConfig {
+            auto_reload: true,
+            debounce: Duration::from_millis(100),
+        };
+        let (sender, receiver) = channel();
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "a: {cmd: ls}").unwrap();
+        Watcher::try_from(path.to_str().unwrap())
+            .unwrap()
+            .configure(config)
+            .run(sender);
+
+        fs::write(dir.join("other.yml"), "").unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+
+        for _ in 0..3 {
+            fs::write(dir.join("config.yml.tmp"), "b: {cmd: ls}").unwrap();
+            fs::rename(dir.join("config.yml.tmp"), &path).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        let reload = receiver.recv_timeout(Duration::from_secs(2));
+        assert!(matches!(reload, Ok(Inter::Reload)));
+        assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+
+        fs::write(&path, "c: {cmd: ls}").unwrap();
+        let reload = receiver.recv_timeout(Duration::from_secs(2));
+        assert!(matches!(reload, Ok(Inter::Reload)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,11 +6,15 @@ pub enum Taskmaster {
     Io(std::io::Error),
     ParseToml(toml::de::Error),
     ParseYaml(serde_yaml::Error),
-    Signal,
+    Dependency(String),
+    Group(String),
     Cli,
     InvalidConf,
     InvalidCmd,
-    ForkFailed
+    SocketInUse,
+    NotASocket(String),
+    ForkFailed,
+    Daemon(String),
 }
 
 impl Taskmaster {
@@ -20,18 +24,35 @@ impl Taskmaster {
             Taskmaster::Io(_) => "IO failure",
             Taskmaster::ParseToml(_) => "Unable to parse config file in TOML format",
             Taskmaster::ParseYaml(_) => "Unable to parse config file in YAML format",
-            Taskmaster::Signal => "Signal not handled",
+            Taskmaster::Dependency(_) => "Invalid task dependencies",
+            Taskmaster::Group(_) => "Invalid task group",
             Taskmaster::Cli => "Error in the cli",
             Taskmaster::InvalidConf => "Config file path is invald",
             Taskmaster::InvalidCmd => "Invalid Command",
-            Taskmaster::ForkFailed => "Fork Failed"
+            Taskmaster::SocketInUse => "Control socket is used by another server",
+            Taskmaster::NotASocket(_) => "Control socket path is not a socket",
+            Taskmaster::ForkFailed => "Fork Failed",
+            Taskmaster::Daemon(_) => "Daemon failed to start",
         }
     }
 }
 
 impl std::fmt::Display for Taskmaster {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.__description().fmt(f)
+        match self {
+            Taskmaster::ReadFile(e) | Taskmaster::Io(e) => {
+                write!(f, "{}: {}", self.__description(), e)
+            }
+            Taskmaster::ParseToml(e) => write!(f, "{}: {}", self.__description(), e),
+            Taskmaster::ParseYaml(e) => write!(f, "{}: {}", self.__description(), e),
+            Taskmaster::Dependency(e)
+            | Taskmaster::Group(e)
+            | Taskmaster::NotASocket(e)
+            | Taskmaster::Daemon(e) => {
+                write!(f, "{}: {}", self.__description(), e)
+            }
+            _ => self.__description().fmt(f),
+        }
     }
 }
 
@@ -45,11 +66,15 @@ impl error::Error for Taskmaster {
             Taskmaster::ReadFile(ref e) | Taskmaster::Io(ref e) => Some(e),
             Taskmaster::ParseToml(ref e) => Some(e),
             Taskmaster::ParseYaml(ref e) => Some(e),
-            Taskmaster::Signal
+            Taskmaster::Dependency(_)
+            | Taskmaster::Group(_)
             | Taskmaster::Cli
             | Taskmaster::ForkFailed
+            | Taskmaster::Daemon(_)
             | Taskmaster::InvalidConf
-            | Taskmaster::InvalidCmd => None,
+            | Taskmaster::InvalidCmd
+            | Taskmaster::SocketInUse
+            | Taskmaster::NotASocket(_) => None,
         }
     }
 }
@@ -16,6 +16,7 @@ impl Logger {
         set_boxed_logger(Self::new(level, config, file))
     }
 
+    #[allow(clippy::unnecessary_box_returns)]
     pub fn new(level: LevelFilter, config: Config, file: File) -> Box<Self> {
         Box::new(Self {
             level,
@@ -13,6 +13,7 @@ impl Logger {
         set_boxed_logger(Self::new(level, config))
     }
 
+    #[allow(clippy::unnecessary_box_returns)]
     pub fn new(level: LevelFilter, config: Config) -> Box<Self> {
         Box::new(Self { level, config })
     }
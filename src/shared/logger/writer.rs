@@ -9,8 +9,8 @@ pub fn write_log<W>(config: &Config, record: &Record<'_>, writer: &mut W) -> Res
 where
     W: Write,
 {
-    if config.instant.is_some() {
-        write_instant_with_level(writer, &config.instant.unwrap(), record.level())?;
+    if let Some(instant) = config.instant {
+        write_instant_with_level(writer, &instant, record.level())?;
     } else {
         write_level(writer, record.level())?;
     }
@@ -39,7 +39,7 @@ fn write_level<W>(writer: &mut W, level: Level) -> Result<(), Error>
 where
     W: Write,
 {
-    write!(writer, "[{:5}] ", level)?;
+    write!(writer, "[{level:5}] ")?;
     Ok(())
 }
 
@@ -1,14 +1,55 @@
 use serde::{Deserialize, Serialize};
 
+// Task ids starting with it designate every task of a group
+pub const GROUP_PREFIX: &str = "group:";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 pub enum Message {
-    Reload,
-    Start { id: String },
-    Info { id: String },
-    Stop { id: String },
-    Status { id: String },
-    Restart { id: String },
+    Reload {
+        #[serde(default)]
+        dry_run: bool,
+    },
+    Start {
+        id: String,
+    },
+    Info {
+        id: String,
+    },
+    Stop {
+        id: String,
+    },
+    Status {
+        id: String,
+    },
+    Restart {
+        id: String,
+    },
+    Pause {
+        id: String,
+    },
+    Resume {
+        id: String,
+    },
+    Tail {
+        id: String,
+        lines: usize,
+    },
+    Follow {
+        id: String,
+    },
+    // signal is a name like HUP or SIGHUP, checked by the server
+    Signal {
+        id: String,
+        signal: String,
+        #[serde(default)]
+        instance: Option<u32>,
+    },
     List,
-    Quit,
+    Quit {
+        #[serde(default)]
+        keep_children: bool,
+    },
+    // execute the server again in place, keeping the tasks running
+    Handover,
 }